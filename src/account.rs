@@ -1,21 +1,14 @@
 use std::collections::BTreeMap;
 
-use serde_json::from_str;
-
-use crate::client::Client;
+use crate::client::{parse_response, Client};
 // use crate::client::*;
 use crate::error::*;
 use crate::model::*;
-use crate::util::*;
+use crate::numeric::{is_finite, to_parameter, Number, ZERO};
+use crate::order::{NewOrder, OrderSide};
 
 // use crate::error::APIError;
 
-static ORDER_TYPE_LIMIT: &str = "LIMIT";
-static ORDER_TYPE_MARKET: &str = "MARKET";
-static ORDER_SIDE_BUY: &str = "BUY";
-static ORDER_SIDE_SELL: &str = "SELL";
-static TIME_IN_FORCE_GTC: &str = "GTC";
-
 static API_V3_ORDER: &str = "/api/v3/order";
 
 /// Endpoint for test orders.
@@ -31,8 +24,8 @@ pub struct Account {
 
 struct OrderRequest {
     pub symbol: String,
-    pub qty: f64,
-    pub price: f64,
+    pub qty: Number,
+    pub price: Number,
     pub order_side: String,
     pub order_type: String,
     pub time_in_force: String,
@@ -43,9 +36,9 @@ impl Account {
     pub async fn get_account(&self) -> Result<AccountInformation, BinanceErr> {
         let parameters: BTreeMap<String, String> = BTreeMap::new();
 
-        let request = build_signed_request(parameters, self.recv_window)?;
+        let request = self.client.build_signed_request(parameters, self.recv_window)?;
         let data = self.client.get_signed("/api/v3/account", &request).await?;
-        let account_info: AccountInformation = from_str(data.as_str())?;
+        let account_info: AccountInformation = parse_response("/api/v3/account", &data)?;
 
         Ok(account_info)
     }
@@ -77,9 +70,9 @@ impl Account {
         let mut parameters: BTreeMap<String, String> = BTreeMap::new();
         parameters.insert("symbol".into(), symbol.into());
 
-        let request = build_signed_request(parameters, self.recv_window)?;
+        let request = self.client.build_signed_request(parameters, self.recv_window)?;
         let data = self.client.get_signed("/api/v3/openOrders", &request).await?;
-        let order: Vec<Order> = from_str(data.as_str())?;
+        let order: Vec<Order> = parse_response("/api/v3/openOrders", &data)?;
 
         Ok(order)
     }
@@ -88,9 +81,9 @@ impl Account {
     pub async fn get_all_open_orders(&self) -> Result<Vec<Order>, BinanceErr> {
         let parameters: BTreeMap<String, String> = BTreeMap::new();
 
-        let request = build_signed_request(parameters, self.recv_window)?;
+        let request = self.client.build_signed_request(parameters, self.recv_window)?;
         let data = self.client.get_signed("/api/v3/openOrders", &request).await?;
-        let order: Vec<Order> = from_str(data.as_str())?;
+        let order: Vec<Order> = parse_response("/api/v3/openOrders", &data)?;
 
         Ok(order)
     }
@@ -100,7 +93,7 @@ impl Account {
     {
         let mut parameters: BTreeMap<String, String> = BTreeMap::new();
         parameters.insert("symbol".into(), symbol.into());
-        let request = build_signed_request(parameters, self.recv_window)?;
+        let request = self.client.build_signed_request(parameters, self.recv_window)?;
         self.client.delete_signed("/api/v3/openOrders", &request).await?;
         Ok(())
     }
@@ -111,9 +104,9 @@ impl Account {
     {
         let mut parameters: BTreeMap<String, String> = BTreeMap::new();
         parameters.insert("symbol".into(), symbol.into());
-        let request = build_signed_request(parameters, self.recv_window)?;
+        let request = self.client.build_signed_request(parameters, self.recv_window)?;
         let data = self.client.delete_signed("/api/v3/openOrders", &request).await?;
-        let order: Vec<Order> = from_str(data.as_str())?;
+        let order: Vec<Order> = parse_response("/api/v3/openOrders", &data)?;
 
         Ok(order)
     }
@@ -127,9 +120,9 @@ impl Account {
         parameters.insert("symbol".into(), symbol.into());
         parameters.insert("orderId".into(), order_id.to_string());
 
-        let request = build_signed_request(parameters, self.recv_window)?;
+        let request = self.client.build_signed_request(parameters, self.recv_window)?;
         let data = self.client.get_signed(API_V3_ORDER, &request).await?;
-        let order: Order = from_str(data.as_str())?;
+        let order: Order = parse_response(API_V3_ORDER, &data)?;
 
         Ok(order)
     }
@@ -145,125 +138,86 @@ impl Account {
         parameters.insert("symbol".into(), symbol.into());
         parameters.insert("orderId".into(), order_id.to_string());
 
-        let request = build_signed_request(parameters, self.recv_window)?;
+        let request = self.client.build_signed_request(parameters, self.recv_window)?;
         let data = self.client.get_signed(API_V3_ORDER_TEST, &request).await?;
-        let _: TestResponse = from_str(data.as_str())?;
+        let _: TestResponse = parse_response(API_V3_ORDER_TEST, &data)?;
+
+        Ok(())
+    }
+
+    /// Place an order of any type, checked with `NewOrder::validate` before it is sent
+    pub async fn place_order(&self, order: NewOrder) -> Result<Transaction, BinanceErr> {
+        order.validate()?;
+        let request = self.client.build_signed_request(order.to_parameters(), self.recv_window)?;
+        let data = self.client.post_signed(API_V3_ORDER, &request).await?;
+        let transaction: Transaction = parse_response(API_V3_ORDER, &data)?;
+
+        Ok(transaction)
+    }
+
+    /// Place a test order of any type
+    ///
+    /// This order is sandboxed: it is validated, but not sent to the matching engine.
+    pub async fn test_order(&self, order: NewOrder) -> Result<(), BinanceErr> {
+        order.validate()?;
+        let request = self.client.build_signed_request(order.to_parameters(), self.recv_window)?;
+        let data = self.client.post_signed(API_V3_ORDER_TEST, &request).await?;
+        let _: TestResponse = parse_response(API_V3_ORDER_TEST, &data)?;
 
         Ok(())
     }
 
     // Place a LIMIT order - BUY
-    pub async fn limit_buy<S, F>(&self, symbol: S, qty: F, price: f64) -> Result<Transaction, BinanceErr>
+    pub async fn limit_buy<S, F, P>(&self, symbol: S, qty: F, price: P) -> Result<Transaction, BinanceErr>
         where
             S: Into<String>,
-            F: Into<f64>,
+            F: Into<Number>,
+            P: Into<Number>,
     {
-        let buy: OrderRequest = OrderRequest {
-            symbol: symbol.into(),
-            qty: qty.into(),
-            price,
-            order_side: ORDER_SIDE_BUY.to_string(),
-            order_type: ORDER_TYPE_LIMIT.to_string(),
-            time_in_force: TIME_IN_FORCE_GTC.to_string(),
-        };
-        let order = self.build_order(buy).await;
-        let request = build_signed_request(order, self.recv_window)?;
-        let data = self.client.post_signed(API_V3_ORDER, &request).await?;
-        let transaction: Transaction = from_str(data.as_str())?;
-
-        Ok(transaction)
+        self.place_order(NewOrder::limit(symbol, OrderSide::Buy, qty.into(), price.into())).await
     }
 
     /// Place a test limit order - BUY
     ///
     /// This order is sandboxed: it is validated, but not sent to the matching engine.
-    pub async fn test_limit_buy<S, F>(&self, symbol: S, qty: F, price: f64) -> Result<(), BinanceErr>
+    pub async fn test_limit_buy<S, F, P>(&self, symbol: S, qty: F, price: P) -> Result<(), BinanceErr>
         where
             S: Into<String>,
-            F: Into<f64>,
+            F: Into<Number>,
+            P: Into<Number>,
     {
-        let buy: OrderRequest = OrderRequest {
-            symbol: symbol.into(),
-            qty: qty.into(),
-            price,
-            order_side: ORDER_SIDE_BUY.to_string(),
-            order_type: ORDER_TYPE_LIMIT.to_string(),
-            time_in_force: TIME_IN_FORCE_GTC.to_string(),
-        };
-        let order = self.build_order(buy).await;
-        let request = build_signed_request(order, self.recv_window)?;
-        let data = self.client.post_signed(API_V3_ORDER_TEST, &request).await?;
-        let _: TestResponse = from_str(data.as_str())?;
-
-        Ok(())
+        self.test_order(NewOrder::limit(symbol, OrderSide::Buy, qty.into(), price.into())).await
     }
 
     // Place a LIMIT order - SELL
-    pub async fn limit_sell<S, F>(&self, symbol: S, qty: F, price: f64) -> Result<Transaction, BinanceErr>
+    pub async fn limit_sell<S, F, P>(&self, symbol: S, qty: F, price: P) -> Result<Transaction, BinanceErr>
         where
             S: Into<String>,
-            F: Into<f64>,
+            F: Into<Number>,
+            P: Into<Number>,
     {
-        let sell: OrderRequest = OrderRequest {
-            symbol: symbol.into(),
-            qty: qty.into(),
-            price,
-            order_side: ORDER_SIDE_SELL.to_string(),
-            order_type: ORDER_TYPE_LIMIT.to_string(),
-            time_in_force: TIME_IN_FORCE_GTC.to_string(),
-        };
-        let order = self.build_order(sell).await;
-        let request = build_signed_request(order, self.recv_window)?;
-        let data = self.client.post_signed(API_V3_ORDER, &request).await?;
-        let transaction: Transaction = from_str(data.as_str())?;
-
-        Ok(transaction)
+        self.place_order(NewOrder::limit(symbol, OrderSide::Sell, qty.into(), price.into())).await
     }
 
     /// Place a test LIMIT order - SELL
     ///
     /// This order is sandboxed: it is validated, but not sent to the matching engine.
-    pub async fn test_limit_sell<S, F>(&self, symbol: S, qty: F, price: f64) -> Result<(), BinanceErr>
+    pub async fn test_limit_sell<S, F, P>(&self, symbol: S, qty: F, price: P) -> Result<(), BinanceErr>
         where
             S: Into<String>,
-            F: Into<f64>,
+            F: Into<Number>,
+            P: Into<Number>,
     {
-        let sell: OrderRequest = OrderRequest {
-            symbol: symbol.into(),
-            qty: qty.into(),
-            price,
-            order_side: ORDER_SIDE_SELL.to_string(),
-            order_type: ORDER_TYPE_LIMIT.to_string(),
-            time_in_force: TIME_IN_FORCE_GTC.to_string(),
-        };
-        let order = self.build_order(sell).await;
-        let request = build_signed_request(order, self.recv_window)?;
-        let data = self.client.post_signed(API_V3_ORDER_TEST, &request).await?;
-        let _: TestResponse = from_str(data.as_str())?;
-
-        Ok(())
+        self.test_order(NewOrder::limit(symbol, OrderSide::Sell, qty.into(), price.into())).await
     }
 
     // Place a MARKET order - BUY
     pub async fn market_buy<S, F>(&self, symbol: S, qty: F) -> Result<Transaction, BinanceErr>
         where
             S: Into<String>,
-            F: Into<f64>,
+            F: Into<Number>,
     {
-        let buy: OrderRequest = OrderRequest {
-            symbol: symbol.into(),
-            qty: qty.into(),
-            price: 0.0,
-            order_side: ORDER_SIDE_BUY.to_string(),
-            order_type: ORDER_TYPE_MARKET.to_string(),
-            time_in_force: TIME_IN_FORCE_GTC.to_string(),
-        };
-        let order = self.build_order(buy).await;
-        let request = build_signed_request(order, self.recv_window)?;
-        let data = self.client.post_signed(API_V3_ORDER, &request).await?;
-        let transaction: Transaction = from_str(data.as_str())?;
-
-        Ok(transaction)
+        self.place_order(NewOrder::market(symbol, OrderSide::Buy, qty.into())).await
     }
 
     /// Place a test MARKET order - BUY
@@ -272,44 +226,18 @@ impl Account {
     pub async fn test_market_buy<S, F>(&self, symbol: S, qty: F) -> Result<(), BinanceErr>
         where
             S: Into<String>,
-            F: Into<f64>,
+            F: Into<Number>,
     {
-        let buy: OrderRequest = OrderRequest {
-            symbol: symbol.into(),
-            qty: qty.into(),
-            price: 0.0,
-            order_side: ORDER_SIDE_BUY.to_string(),
-            order_type: ORDER_TYPE_MARKET.to_string(),
-            time_in_force: TIME_IN_FORCE_GTC.to_string(),
-        };
-        let order = self.build_order(buy).await;
-        let request = build_signed_request(order, self.recv_window)?;
-        let data = self.client.post_signed(API_V3_ORDER_TEST, &request).await?;
-        let _: TestResponse = from_str(data.as_str())?;
-
-        Ok(())
+        self.test_order(NewOrder::market(symbol, OrderSide::Buy, qty.into())).await
     }
 
     // Place a MARKET order - SELL
     pub async fn market_sell<S, F>(&self, symbol: S, qty: F) -> Result<Transaction, BinanceErr>
         where
             S: Into<String>,
-            F: Into<f64>,
+            F: Into<Number>,
     {
-        let sell: OrderRequest = OrderRequest {
-            symbol: symbol.into(),
-            qty: qty.into(),
-            price: 0.0,
-            order_side: ORDER_SIDE_SELL.to_string(),
-            order_type: ORDER_TYPE_MARKET.to_string(),
-            time_in_force: TIME_IN_FORCE_GTC.to_string(),
-        };
-        let order = self.build_order(sell).await;
-        let request = build_signed_request(order, self.recv_window)?;
-        let data = self.client.post_signed(API_V3_ORDER, &request).await?;
-        let transaction: Transaction = from_str(data.as_str())?;
-
-        Ok(transaction)
+        self.place_order(NewOrder::market(symbol, OrderSide::Sell, qty.into())).await
     }
 
     /// Place a test MARKET order - SELL
@@ -318,38 +246,25 @@ impl Account {
     pub async fn test_market_sell<S, F>(&self, symbol: S, qty: F) -> Result<(), BinanceErr>
         where
             S: Into<String>,
-            F: Into<f64>,
+            F: Into<Number>,
     {
-        let sell: OrderRequest = OrderRequest {
-            symbol: symbol.into(),
-            qty: qty.into(),
-            price: 0.0,
-            order_side: ORDER_SIDE_SELL.to_string(),
-            order_type: ORDER_TYPE_MARKET.to_string(),
-            time_in_force: TIME_IN_FORCE_GTC.to_string(),
-        };
-        let order = self.build_order(sell).await;
-        let request = build_signed_request(order, self.recv_window)?;
-        let data = self.client.post_signed(API_V3_ORDER_TEST, &request).await?;
-        let _: TestResponse = from_str(data.as_str())?;
-
-        Ok(())
+        self.test_order(NewOrder::market(symbol, OrderSide::Sell, qty.into())).await
     }
 
-
     /// Place a custom order
+    #[deprecated(note = "use `Account::place_order` with a `NewOrder`")]
     pub async fn custom_order<S, F>(
         &self,
         symbol: S,
         qty: F,
-        price: f64,
+        price: Number,
         order_side: S,
         order_type: S,
         execution_type: S,
     ) -> Result<Transaction, BinanceErr>
         where
             S: Into<String>,
-            F: Into<f64>,
+            F: Into<Number>,
     {
         let sell: OrderRequest = OrderRequest {
             symbol: symbol.into(),
@@ -359,10 +274,10 @@ impl Account {
             order_type: order_type.into(),
             time_in_force: execution_type.into(),
         };
-        let order = self.build_order(sell).await;
-        let request = build_signed_request(order, self.recv_window)?;
+        let order = self.build_order(sell).await?;
+        let request = self.client.build_signed_request(order, self.recv_window)?;
         let data = self.client.post_signed(API_V3_ORDER, &request).await?;
-        let transaction: Transaction = from_str(data.as_str())?;
+        let transaction: Transaction = parse_response(API_V3_ORDER, &data)?;
 
         Ok(transaction)
     }
@@ -371,18 +286,19 @@ impl Account {
     /// Place a test custom order
     ///
     /// This order is sandboxed: it is validated, but not sent to the matching engine.
+    #[deprecated(note = "use `Account::test_order` with a `NewOrder`")]
     pub async fn test_custom_order<S, F>(
         &self,
         symbol: S,
         qty: F,
-        price: f64,
+        price: Number,
         order_side: S,
         order_type: S,
         execution_type: S,
     ) -> Result<(), BinanceErr>
         where
             S: Into<String>,
-            F: Into<f64>,
+            F: Into<Number>,
     {
         let sell: OrderRequest = OrderRequest {
             symbol: symbol.into(),
@@ -392,10 +308,10 @@ impl Account {
             order_type: order_type.into(),
             time_in_force: execution_type.into(),
         };
-        let order = self.build_order(sell).await;
-        let request = build_signed_request(order, self.recv_window)?;
+        let order = self.build_order(sell).await?;
+        let request = self.client.build_signed_request(order, self.recv_window)?;
         let data = self.client.post_signed(API_V3_ORDER_TEST, &request).await?;
-        let _: TestResponse = from_str(data.as_str())?;
+        let _: TestResponse = parse_response(API_V3_ORDER_TEST, &data)?;
 
         Ok(())
     }
@@ -409,9 +325,9 @@ impl Account {
         parameters.insert("symbol".into(), symbol.into());
         parameters.insert("orderId".into(), order_id.to_string());
 
-        let request = build_signed_request(parameters, self.recv_window)?;
+        let request = self.client.build_signed_request(parameters, self.recv_window)?;
         let data = self.client.delete_signed(API_V3_ORDER, &request).await?;
-        let order_canceled: OrderCanceled = from_str(data.as_str())?;
+        let order_canceled: OrderCanceled = parse_response(API_V3_ORDER, &data)?;
 
         Ok(order_canceled)
     }
@@ -427,9 +343,9 @@ impl Account {
         parameters.insert("symbol".into(), symbol.into());
         parameters.insert("orderId".into(), order_id.to_string());
 
-        let request = build_signed_request(parameters, self.recv_window)?;
+        let request = self.client.build_signed_request(parameters, self.recv_window)?;
         let data = self.client.delete_signed(API_V3_ORDER_TEST, &request).await?;
-        let _: TestResponse = from_str(data.as_str())?;
+        let _: TestResponse = parse_response(API_V3_ORDER_TEST, &data)?;
 
         Ok(())
     }
@@ -442,26 +358,29 @@ impl Account {
         let mut parameters: BTreeMap<String, String> = BTreeMap::new();
         parameters.insert("symbol".into(), symbol.into());
 
-        let request = build_signed_request(parameters, self.recv_window)?;
+        let request = self.client.build_signed_request(parameters, self.recv_window)?;
         let data = self.client.get_signed("/api/v3/myTrades", &request).await?;
-        let trade_history: Vec<TradeHistory> = from_str(data.as_str())?;
+        let trade_history: Vec<TradeHistory> = parse_response("/api/v3/myTrades", &data)?;
 
         Ok(trade_history)
     }
 
-    async fn build_order(&self, order: OrderRequest) -> BTreeMap<String, String> {
+    async fn build_order(&self, order: OrderRequest) -> Result<BTreeMap<String, String>, BinanceErr> {
+        if !is_finite(order.qty) || !is_finite(order.price) {
+            return Err(BinanceErr::InvalidOrder("quantity and price must be finite".into()));
+        }
         let mut order_parameters: BTreeMap<String, String> = BTreeMap::new();
 
         order_parameters.insert("symbol".into(), order.symbol);
         order_parameters.insert("side".into(), order.order_side);
         order_parameters.insert("type".into(), order.order_type);
-        order_parameters.insert("quantity".into(), order.qty.to_string());
+        order_parameters.insert("quantity".into(), to_parameter(order.qty));
 
-        if order.price != 0.0 {
-            order_parameters.insert("price".into(), order.price.to_string());
+        if order.price != ZERO {
+            order_parameters.insert("price".into(), to_parameter(order.price));
             order_parameters.insert("timeInForce".into(), order.time_in_force);
         }
 
-        order_parameters
+        Ok(order_parameters)
     }
 }
@@ -1,59 +1,71 @@
+use std::collections::HashMap;
+
 use crate::account::*;
 use crate::binance_futures::general::*;
 use crate::binance_futures::market::*;
 use crate::client::*;
+use crate::config::Config;
 use crate::general::*;
 use crate::market::*;
 use crate::userstream::*;
 use crate::websocket::Websocket;
 
-static API_HOST: &str = "https://api.binance.com";
-static FAPI_HOST: &str = "https://fapi.binance.com";
-
 //#[derive(Clone)]
-pub trait Binance {
-    fn new(api_key: Option<String>, secret_key: Option<String>) -> Self;
+pub trait Binance: Sized {
+    fn new(api_key: Option<String>, secret_key: Option<String>) -> Self {
+        Self::new_with_config(api_key, secret_key, &Config::default())
+    }
+
+    fn new_with_config(api_key: Option<String>, secret_key: Option<String>, config: &Config) -> Self {
+        let client = Client::new_with_config(api_key, secret_key, config.rest_api_endpoint.clone(), config);
+        Self::new_with_client(&client, config)
+    }
+
+    /// Build a handle on top of an existing `Client`, sharing its keys and connection pool
+    fn new_with_client(client: &Client, config: &Config) -> Self;
 }
 
 impl Binance for General {
-    fn new(api_key: Option<String>, secret_key: Option<String>) -> General {
+    fn new_with_client(client: &Client, _: &Config) -> General {
         General {
-            client: Client::new(api_key, secret_key, API_HOST.to_string()),
+            client: client.clone(),
         }
     }
 }
 
 impl Binance for Account {
-    fn new(api_key: Option<String>, secret_key: Option<String>) -> Account {
+    fn new_with_client(client: &Client, config: &Config) -> Account {
         Account {
-            client: Client::new(api_key, secret_key, API_HOST.to_string()),
-            recv_window: 5000,
+            client: client.clone(),
+            recv_window: config.recv_window,
         }
     }
 }
 
 impl Binance for Market {
-    fn new(api_key: Option<String>, secret_key: Option<String>) -> Market {
+    fn new_with_client(client: &Client, config: &Config) -> Market {
         Market {
-            client: Client::new(api_key, secret_key, API_HOST.to_string()),
-            recv_window: 5000,
+            client: client.clone(),
+            recv_window: config.recv_window,
         }
     }
 }
 
 impl Binance for UserStream {
-    fn new(api_key: Option<String>, secret_key: Option<String>) -> UserStream {
+    fn new_with_client(client: &Client, config: &Config) -> UserStream {
         UserStream {
-            client: Client::new(api_key, secret_key, API_HOST.to_string()),
-            recv_window: 5000,
-            ws: None,
+            client: client.clone(),
+            recv_window: config.recv_window,
+            ws_endpoint: config.ws_endpoint.clone(),
+            keepalive_interval: LISTEN_KEY_KEEPALIVE,
+            listen_keys: HashMap::new(),
         }
     }
 }
 
 impl Binance for Websocket {
-    fn new(_: Option<String>, _: Option<String>) -> Self {
-        Websocket::new()
+    fn new_with_client(_: &Client, config: &Config) -> Self {
+        Websocket::new_with_config(config)
     }
 }
 
@@ -62,18 +74,18 @@ impl Binance for Websocket {
 // *****************************************************
 
 impl Binance for FuturesGeneral {
-    fn new(api_key: Option<String>, secret_key: Option<String>) -> FuturesGeneral {
+    fn new_with_client(client: &Client, config: &Config) -> FuturesGeneral {
         FuturesGeneral {
-            client: Client::new(api_key, secret_key, FAPI_HOST.to_string()),
+            client: client.with_host(config.futures_rest_api_endpoint.clone().unwrap_or_default()),
         }
     }
 }
 
 impl Binance for FuturesMarket {
-    fn new(api_key: Option<String>, secret_key: Option<String>) -> FuturesMarket {
+    fn new_with_client(client: &Client, config: &Config) -> FuturesMarket {
         FuturesMarket {
-            client: Client::new(api_key, secret_key, FAPI_HOST.to_string()),
-            recv_window: 5000,
+            client: client.with_host(config.futures_rest_api_endpoint.clone().unwrap_or_default()),
+            recv_window: config.recv_window,
         }
     }
 }
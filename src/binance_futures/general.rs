@@ -1,8 +1,9 @@
-use serde_json::from_str;
+use std::time::Duration;
 
 use crate::binance_futures::model::*;
 use crate::client::*;
 use crate::error::*;
+use crate::time_sync::TimeSyncTask;
 
 #[derive(Clone)]
 pub struct FuturesGeneral {
@@ -19,16 +20,28 @@ impl FuturesGeneral {
     // Check server time
     pub async fn get_server_time(&self) -> Result<ServerTime, BinanceErr> {
         let data: String = self.client.get("/fapi/v1/time", "").await?;
-        let server_time: ServerTime = from_str(data.as_str())?;
+        let server_time: ServerTime = parse_response("/fapi/v1/time", &data)?;
 
         Ok(server_time)
     }
 
+    // Measure the offset between the local and the server clock, used to timestamp signed requests
+    // on every handle sharing this client
+    pub async fn sync_time(&self) -> Result<(), BinanceErr> {
+        self.client.time_sync().sync(&self.client, "/fapi/v1/time").await
+    }
+
+    // Keep the clock offset up to date in the background, every `interval` until the task is dropped
+    pub fn start_time_sync(&self, interval: Duration) -> TimeSyncTask {
+        self.client.time_sync().start(self.client.clone(), "/fapi/v1/time", interval)
+    }
+
     // Obtain exchange information
     // - Current exchange trading rules and symbol information
     pub async fn exchange_info(&self) -> Result<ExchangeInformation, BinanceErr> {
         let data: String = self.client.get("/fapi/v1/exchangeInfo", "").await?;
-        let info: ExchangeInformation = from_str(data.as_str())?;
+        let info: ExchangeInformation = parse_response("/fapi/v1/exchangeInfo", &data)?;
+        self.client.rate_limiter().seed(&info.rate_limits);
 
         Ok(info)
     }
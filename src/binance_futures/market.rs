@@ -22,7 +22,7 @@
 
 use std::collections::BTreeMap;
 
-use serde_json::{from_str, Value};
+use serde_json::Value;
 
 use crate::binance_futures::model::*;
 use crate::client::*;
@@ -53,7 +53,7 @@ impl FuturesMarket {
 
         let data = self.client.get("/fapi/v1/depth", &request).await?;
 
-        let order_book: OrderBook = from_str(data.as_str())?;
+        let order_book: OrderBook = parse_response("/fapi/v1/depth", &data)?;
 
         Ok(order_book)
     }
@@ -69,7 +69,7 @@ impl FuturesMarket {
 
         let data = self.client.get("/fapi/v1/trades", &request).await?;
 
-        let trades: Trades = from_str(data.as_str())?;
+        let trades: Trades = parse_response("/fapi/v1/trades", &data)?;
 
         Ok(trades)
     }
@@ -95,13 +95,13 @@ impl FuturesMarket {
             parameters.insert("fromId".into(), format!("{}", fi));
         }
 
-        let request = build_signed_request(parameters, self.recv_window)?;
+        let request = self.client.build_signed_request(parameters, self.recv_window)?;
 
         let data = self
             .client
             .get_signed("/fapi/v1/historicalTrades", &request).await?;
 
-        let trades: Trades = from_str(data.as_str())?;
+        let trades: Trades = parse_response("/fapi/v1/historicalTrades", &data)?;
 
         Ok(trades)
     }
@@ -138,7 +138,7 @@ impl FuturesMarket {
 
         let data = self.client.get("/fapi/v1/aggTrades", &request).await?;
 
-        let aggtrades: AggTrades = from_str(data.as_str())?;
+        let aggtrades: AggTrades = parse_response("/fapi/v1/aggTrades", &data)?;
 
         Ok(aggtrades)
     }
@@ -174,25 +174,13 @@ impl FuturesMarket {
         let request = build_request(&parameters);
 
         let data = self.client.get("/fapi/v1/klines", &request).await?;
-        let parsed_data: Vec<Vec<Value>> = from_str(data.as_str())?;
+        let parsed_data: Vec<Vec<Value>> = parse_response("/fapi/v1/klines", &data)?;
 
         let klines = KlineSummaries::AllKlineSummaries(
             parsed_data
                 .iter()
-                .map(|row| KlineSummary {
-                    open_time: to_i64(&row[0]),
-                    open: to_f64(&row[1]),
-                    high: to_f64(&row[2]),
-                    low: to_f64(&row[3]),
-                    close: to_f64(&row[4]),
-                    volume: to_f64(&row[5]),
-                    close_time: to_i64(&row[6]),
-                    quote_asset_volume: to_f64(&row[7]),
-                    number_of_trades: to_i64(&row[8]),
-                    taker_buy_base_asset_volume: to_f64(&row[9]),
-                    taker_buy_quote_asset_volume: to_f64(&row[10]),
-                })
-                .collect(),
+                .map(|row| to_kline_summary(row))
+                .collect::<Result<Vec<KlineSummary>, BinanceErr>>()?,
         );
         Ok(klines)
     }
@@ -209,7 +197,7 @@ impl FuturesMarket {
 
         let data = self.client.get("/fapi/v1/ticker/24hr", &request).await?;
 
-        let stats: PriceStats = from_str(data.as_str())?;
+        let stats: PriceStats = parse_response("/fapi/v1/ticker/24hr", &data)?;
 
         Ok(stats)
     }
@@ -225,7 +213,7 @@ impl FuturesMarket {
         let request = build_request(&parameters);
 
         let data = self.client.get("/fapi/v1/ticker/price", &request).await?;
-        let symbol_price: SymbolPrice = from_str(data.as_str())?;
+        let symbol_price: SymbolPrice = parse_response("/fapi/v1/ticker/price", &data)?;
 
         Ok(symbol_price)
     }
@@ -235,7 +223,7 @@ impl FuturesMarket {
     pub async fn get_all_book_tickers(&self) -> Result<BookTickers, BinanceErr> {
         let data = self.client.get("/fapi/v1/ticker/bookTicker", "").await?;
 
-        let book_tickers: BookTickers = from_str(data.as_str())?;
+        let book_tickers: BookTickers = parse_response("/fapi/v1/ticker/bookTicker", &data)?;
 
         Ok(book_tickers)
     }
@@ -251,7 +239,7 @@ impl FuturesMarket {
         let request = build_request(&parameters);
 
         let data = self.client.get("/fapi/v1/ticker/bookTicker", &request).await?;
-        let ticker: Tickers = from_str(data.as_str())?;
+        let ticker: Tickers = parse_response("/fapi/v1/ticker/bookTicker", &data)?;
 
         Ok(ticker)
     }
@@ -259,14 +247,14 @@ impl FuturesMarket {
     pub async fn get_mark_prices(&self) -> Result<MarkPrices, BinanceErr> {
         let data = self.client.get("/fapi/v1/premiumIndex", "").await?;
 
-        let mark_prices: MarkPrices = from_str(data.as_str())?;
+        let mark_prices: MarkPrices = parse_response("/fapi/v1/premiumIndex", &data)?;
 
         Ok(mark_prices)
     }
 
     pub async fn get_all_liquidation_orders(&self) -> Result<LiquidationOrders, BinanceErr> {
         let data = self.client.get("/fapi/v1/allForceOrders", "").await?;
-        let liquidation_orders: LiquidationOrders = from_str(data.as_str())?;
+        let liquidation_orders: LiquidationOrders = parse_response("/fapi/v1/allForceOrders", &data)?;
 
         Ok(liquidation_orders)
     }
@@ -281,7 +269,7 @@ impl FuturesMarket {
         let request = build_request(&parameters);
 
         let data = self.client.get("/fapi/v1/openInterest", &request).await?;
-        let open_interest: OpenInterest = from_str(data.as_str())?;
+        let open_interest: OpenInterest = parse_response("/fapi/v1/openInterest", &data)?;
 
         Ok(open_interest)
     }
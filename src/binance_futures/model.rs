@@ -5,6 +5,7 @@ pub use crate::model::{
     SymbolPrice, Tickers,
 };
 use crate::model::string_or_float;
+use crate::numeric::Number;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -21,8 +22,10 @@ pub struct ExchangeInformation {
 pub struct Symbol {
     pub symbol: String,
     pub status: String,
-    pub maint_margin_percent: String,
-    pub required_margin_percent: String,
+    #[serde(with = "string_or_float")]
+    pub maint_margin_percent: Number,
+    #[serde(with = "string_or_float")]
+    pub required_margin_percent: Number,
     pub base_asset: String,
     pub quote_asset: String,
     pub price_precision: u16,
@@ -52,23 +55,26 @@ pub struct OrderBook {
 #[serde(rename_all = "camelCase")]
 pub struct PriceStats {
     pub symbol: String,
-    pub price_change: String,
-    pub price_change_percent: String,
-    pub weighted_avg_price: String,
     #[serde(with = "string_or_float")]
-    pub last_price: f64,
+    pub price_change: Number,
+    #[serde(with = "string_or_float")]
+    pub price_change_percent: Number,
+    #[serde(with = "string_or_float")]
+    pub weighted_avg_price: Number,
+    #[serde(with = "string_or_float")]
+    pub last_price: Number,
     #[serde(with = "string_or_float")]
-    pub open_price: f64,
+    pub open_price: Number,
     #[serde(with = "string_or_float")]
-    pub high_price: f64,
+    pub high_price: Number,
     #[serde(with = "string_or_float")]
-    pub low_price: f64,
+    pub low_price: Number,
     #[serde(with = "string_or_float")]
-    pub volume: f64,
+    pub volume: Number,
     #[serde(with = "string_or_float")]
-    pub quote_volume: f64,
+    pub quote_volume: Number,
     #[serde(with = "string_or_float")]
-    pub last_qty: f64,
+    pub last_qty: Number,
     pub open_time: u64,
     pub close_time: u64,
     pub first_id: u64,
@@ -88,11 +94,11 @@ pub struct Trade {
     pub id: u64,
     pub is_buyer_maker: bool,
     #[serde(with = "string_or_float")]
-    pub price: f64,
+    pub price: Number,
     #[serde(with = "string_or_float")]
-    pub qty: f64,
+    pub qty: Number,
     #[serde(with = "string_or_float")]
-    pub quote_qty: f64,
+    pub quote_qty: Number,
     pub time: u64,
 }
 
@@ -116,9 +122,9 @@ pub struct AggTrade {
     #[serde(rename = "m")]
     pub maker: bool,
     #[serde(rename = "p", with = "string_or_float")]
-    pub price: f64,
+    pub price: Number,
     #[serde(rename = "q", with = "string_or_float")]
-    pub qty: f64,
+    pub qty: Number,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -132,9 +138,9 @@ pub enum MarkPrices {
 pub struct MarkPrice {
     pub symbol: String,
     #[serde(with = "string_or_float")]
-    pub mark_price: f64,
+    pub mark_price: Number,
     #[serde(with = "string_or_float")]
-    pub last_funding_rate: f64,
+    pub last_funding_rate: Number,
     pub next_funding_time: u64,
     pub time: u64,
 }
@@ -149,13 +155,13 @@ pub enum LiquidationOrders {
 #[serde(rename_all = "camelCase")]
 pub struct LiquidationOrder {
     #[serde(with = "string_or_float")]
-    pub average_price: f64,
+    pub average_price: Number,
     #[serde(with = "string_or_float")]
-    pub executed_qty: f64,
+    pub executed_qty: Number,
     #[serde(with = "string_or_float")]
-    pub orig_qty: f64,
+    pub orig_qty: Number,
     #[serde(with = "string_or_float")]
-    pub price: f64,
+    pub price: Number,
     pub side: String,
     pub status: String,
     pub symbol: String,
@@ -168,6 +174,6 @@ pub struct LiquidationOrder {
 #[serde(rename_all = "camelCase")]
 pub struct OpenInterest {
     #[serde(with = "string_or_float")]
-    pub open_interest: f64,
+    pub open_interest: Number,
     pub symbol: String,
 }
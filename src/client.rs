@@ -1,132 +1,329 @@
-use hex::encode as hex_encode;
-use hmac::{Hmac, Mac, NewMac};
-use reqwest::{Response, StatusCode};
-use reqwest::header::{CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue, USER_AGENT};
-use sha2::Sha256;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use crate::error::BinanceErr;
+use reqwest::{Method, StatusCode};
+use serde::de::DeserializeOwned;
+use reqwest::header::{CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+
+use crate::config::Config;
+use crate::error::{BinanceErr, BinanceErrorCode};
 use crate::error::other_err::*;
+use crate::rate_limit::{request_weight, RateLimiter};
+use crate::retry::RetryPolicy;
+use crate::signer::{HmacSigner, Signer};
+use crate::time_sync::{time_endpoint, TimeSync};
+use crate::transport::{ReqwestTransport, Transport, TransportRequest, TransportResponse, UnavailableTransport};
+use crate::util::{build_signed_request_at, refresh_request_timestamp};
+
+/// Used when a 429/418 comes without a `Retry-After` header
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(60);
+/// What Binance assumes when a signed request has no `recvWindow`
+const DEFAULT_RECV_WINDOW: u64 = 5000;
+/// Allowance for the error of the measured clock offset, on top of a `recvWindow`
+const CLOCK_MARGIN: Duration = Duration::from_millis(500);
 
+/// New orders, which count towards the `ORDERS` limits
+const ORDER_ENDPOINTS: [&str; 9] = [
+    "/api/v3/order",
+    "/api/v3/order/oco",
+    "/api/v3/order/cancelReplace",
+    "/api/v3/orderList/oco",
+    "/api/v3/orderList/oto",
+    "/api/v3/orderList/otoco",
+    "/api/v3/sor/order",
+    "/fapi/v1/order",
+    "/fapi/v1/batchOrders",
+];
+
+/// Deserialize the body of a successful response from `endpoint`.
+/// A body without the expected shape is a `BinanceErr::MalformedPayload` naming the endpoint.
+pub(crate) fn parse_response<T: DeserializeOwned>(endpoint: &str, data: &str) -> Result<T, BinanceErr> {
+    serde_json::from_str(data).map_err(|e| BinanceErr::MalformedPayload {
+        msg: e.to_string(),
+        payload: data.into(),
+        endpoint: Some(endpoint.into()),
+    })
+}
+
+/// Cloning a `Client` is cheap, every clone shares the same connection pool (transport),
+/// and so does every client built from a `Config` with the same HTTP settings.
 #[derive(Clone)]
 pub struct Client {
     api_key: String,
-    secret_key: String,
+    signer: Arc<dyn Signer>,
     host: String,
+    transport: Arc<dyn Transport>,
+    rate_limiter: RateLimiter,
+    /// The limiter of every host, shared by all the clients derived from this one with `with_host`
+    rate_limiters: Arc<Mutex<HashMap<String, RateLimiter>>>,
+    retry_policy: RetryPolicy,
+    time_sync: TimeSync,
 }
 
 impl Client {
     pub fn new(api_key: Option<String>, secret_key: Option<String>, host: String) -> Self {
+        Client::new_with_config(api_key, secret_key, host, &Config::default())
+    }
+
+    pub fn new_with_config(
+        api_key: Option<String>, secret_key: Option<String>, host: String, config: &Config,
+    ) -> Self {
+        let signer = HmacSigner::new(secret_key.unwrap_or_else(|| "".into()));
+        Client::new_with_signer(api_key, Arc::new(signer), host, config)
+    }
+
+    /// For RSA or Ed25519 API keys, or a signer that keeps the private key elsewhere.
+    /// An HTTP client that cannot be built from `config` fails every request with the reason.
+    pub fn new_with_signer(
+        api_key: Option<String>, signer: Arc<dyn Signer>, host: String, config: &Config,
+    ) -> Self {
+        let transport: Arc<dyn Transport> = match ReqwestTransport::shared(config) {
+            Ok(transport) => transport,
+            Err(e) => Arc::new(UnavailableTransport { reason: e.to_string() }),
+        };
+        let rate_limiter = RateLimiter::new(config.rate_limit_policy);
+        let rate_limiters = Arc::new(Mutex::new(HashMap::from([(host.clone(), rate_limiter.clone())])));
         Client {
             api_key: api_key.unwrap_or_else(|| "".into()),
-            secret_key: secret_key.unwrap_or_else(|| "".into()),
+            signer,
             host,
+            transport,
+            rate_limiter,
+            rate_limiters,
+            retry_policy: config.retry_policy.clone(),
+            time_sync: TimeSync::new(),
         }
     }
 
-    pub async fn get_signed(&self, endpoint: &str, request: &str) -> Result<String, BinanceErr> {
-        let url = self.sign_request(endpoint, request).await;
-        let client = reqwest::Client::new();
-        let response = client
-            .get(url.as_str())
-            .headers(self.build_headers(true)?)
-            .send()
-            .await?;
-
-        self.handler(response).await
+    /// Same keys and connection pool, pointed at another host (e.g. spot -> futures).
+    /// Hosts are rate limited separately, every client for the same host shares its limiter.
+    pub fn with_host(&self, host: String) -> Self {
+        let rate_limiter = self
+            .rate_limiters
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(host.clone())
+            .or_insert_with(|| RateLimiter::new(self.rate_limiter.policy()))
+            .clone();
+        Client { host, rate_limiter, ..self.clone() }
     }
 
-    pub async fn post_signed(&self, endpoint: &str, request: &str) -> Result<String, BinanceErr> {
-        let url = self.sign_request(endpoint, request).await;
-        let client = reqwest::Client::new();
-        let response = client
-            .post(url.as_str())
-            .headers(self.build_headers(true)?)
-            .send()
-            .await?;
+    /// Same client, sending its requests through `transport`, e.g. a `MockTransport` in tests
+    pub fn with_transport(self, transport: Arc<dyn Transport>) -> Self {
+        Client { transport, ..self }
+    }
 
-        self.handler(response).await
+    pub fn host(&self) -> &str {
+        &self.host
     }
 
-    pub async fn delete_signed(&self, endpoint: &str, request: &str) -> Result<String, BinanceErr> {
-        let url = self.sign_request(endpoint, request).await;
-        let client = reqwest::Client::new();
-        let response = client
-            .delete(url.as_str())
-            .headers(self.build_headers(true)?)
-            .send()
-            .await?;
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.rate_limiter
+    }
 
-        self.handler(response).await
+    pub fn time_sync(&self) -> &TimeSync {
+        &self.time_sync
     }
 
-    pub async fn get(&self, endpoint: &str, request: &str) -> Result<String, BinanceErr> {
-        let mut url: String = format!("{}{}", self.host, endpoint);
-        if !request.is_empty() {
-            url.push_str(format!("?{}", request).as_str());
-        }
+    /// Query string for a signed endpoint, stamped with the server-corrected time
+    pub fn build_signed_request(
+        &self, parameters: BTreeMap<String, String>, recv_window: u64,
+    ) -> Result<String, BinanceErr> {
+        Ok(build_signed_request_at(parameters, recv_window, self.time_sync.timestamp()?))
+    }
 
-        let response = reqwest::get(url.as_str()).await?;
+    pub async fn get_signed(&self, endpoint: &str, request: &str) -> Result<String, BinanceErr> {
+        self.send(RequestParts::signed(Method::GET, endpoint, request, self.build_headers(true)?)).await
+    }
 
-        self.handler(response).await
+    pub async fn post_signed(&self, endpoint: &str, request: &str) -> Result<String, BinanceErr> {
+        self.send(RequestParts::signed(Method::POST, endpoint, request, self.build_headers(true)?)).await
     }
 
-    pub async fn post(&self, endpoint: &str) -> Result<String, BinanceErr> {
-        let url: String = format!("{}{}", self.host, endpoint);
+    pub async fn delete_signed(&self, endpoint: &str, request: &str) -> Result<String, BinanceErr> {
+        self.send(RequestParts::signed(Method::DELETE, endpoint, request, self.build_headers(true)?)).await
+    }
 
-        let client = reqwest::Client::new();
-        let response = client
-            .post(url.as_str())
-            .headers(self.build_headers(false)?)
-            .send()
-            .await?;
+    pub async fn get(&self, endpoint: &str, request: &str) -> Result<String, BinanceErr> {
+        self.send(RequestParts::unsigned(Method::GET, endpoint, request, HeaderMap::new(), None)).await
+    }
 
-        self.handler(response).await
+    pub async fn post(&self, endpoint: &str) -> Result<String, BinanceErr> {
+        self.send(RequestParts::unsigned(Method::POST, endpoint, "", self.build_headers(false)?, None)).await
     }
 
     pub async fn put(&self, endpoint: &str, listen_key: &str) -> Result<String, BinanceErr> {
-        let url: String = format!("{}{}", self.host, endpoint);
         let data: String = format!("listenKey={}", listen_key);
-
-        let client = reqwest::Client::new();
-        let response = client
-            .put(url.as_str())
-            .headers(self.build_headers(false)?)
-            .body(data)
-            .send()
-            .await?;
-
-        self.handler(response).await
+        self.send(RequestParts::unsigned(Method::PUT, endpoint, "", self.build_headers(false)?, Some(data))).await
     }
 
     pub async fn delete(&self, endpoint: &str, listen_key: &str) -> Result<String, BinanceErr> {
-        let url: String = format!("{}{}", self.host, endpoint);
         let data: String = format!("listenKey={}", listen_key);
+        self.send(RequestParts::unsigned(Method::DELETE, endpoint, "", self.build_headers(false)?, Some(data))).await
+    }
+
+    async fn send(&self, parts: RequestParts<'_>) -> Result<String, BinanceErr> {
+        if self.host.is_empty() {
+            // e.g. a futures handle on Binance.US, which has no futures market
+            return Err(BinanceErr::from_str(format!("No REST endpoint configured for {}", parts.endpoint)));
+        }
+        let is_order = counts_as_order(&parts.method, parts.endpoint);
+        let weight = request_weight(parts.endpoint, parts.query);
+        // The query of the latest attempt, as sent
+        let mut query = parts.query.to_string();
+        let mut attempt = 1;
+        loop {
+            // Held back by our own limiter: nothing was sent, and retrying would only wait again
+            let permit = self.rate_limiter.acquire(parts.endpoint, weight, is_order).await?;
+            let sent = self.send_once(&parts, &query).await;
+            drop(permit);
+
+            let error = match sent {
+                Ok(data) => return Ok(data),
+                Err(e) => e,
+            };
+            if attempt >= self.retry_policy.max_attempts {
+                return Err(error);
+            }
+            match self.retry_delay(&parts, &query, &error, attempt).await {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => return Err(error),
+            }
+            attempt += 1;
+            if error.error_code() == Some(BinanceErrorCode::InvalidTimestamp) {
+                // Resent with the same offset it would only be rejected again.
+                // On failure the request goes out with the last known offset.
+                self.time_sync.sync(self, time_endpoint(parts.endpoint)).await.ok();
+            }
+            if parts.signed {
+                // Every attempt is a new request to Binance, stamped with the current time
+                query = refresh_request_timestamp(parts.query, self.time_sync.timestamp()?);
+            }
+        }
+    }
+
+    /// A single unsigned GET, without retries
+    pub(crate) async fn get_once(&self, endpoint: &str) -> Result<String, BinanceErr> {
+        let _permit = self.rate_limiter.acquire(endpoint, request_weight(endpoint, ""), false).await?;
+        self.send_once(&RequestParts::unsigned(Method::GET, endpoint, "", HeaderMap::new(), None), "").await
+    }
 
-        let client = reqwest::Client::new();
-        let response = client
-            .delete(url.as_str())
-            .headers(self.build_headers(false)?)
-            .body(data)
-            .send()
+    async fn send_once(&self, parts: &RequestParts<'_>, query: &str) -> Result<String, BinanceErr> {
+        let url = if parts.signed {
+            self.sign_request(parts.endpoint, query).await?
+        } else if query.is_empty() {
+            format!("{}{}", self.host, parts.endpoint)
+        } else {
+            format!("{}{}?{}", self.host, parts.endpoint, query)
+        };
+
+        let response = self
+            .transport
+            .send(TransportRequest {
+                method: parts.method.clone(),
+                url,
+                headers: parts.headers.clone(),
+                body: parts.body.clone(),
+            })
             .await?;
 
-        self.handler(response).await
+        self.handler(parts.endpoint, response)
+    }
+
+    /// How long to wait before resending, or `None` when the error is final or a resend is not safe
+    async fn retry_delay(&self, parts: &RequestParts<'_>, query: &str, error: &BinanceErr, attempt: u32) -> Option<Duration> {
+        let backoff = self.retry_policy.backoff(attempt);
+        let safe_to_resend = match error {
+            // Rejected before processing. The limiter already holds the next attempt back until `retry_after`
+            BinanceErr::RateLimited { retry_after, .. } => *retry_after <= self.retry_policy.max_retry_after,
+            // The request never left
+            BinanceErr::HTTP(e) if e.is_connect() => true,
+            // Sent, but the outcome is unknown
+            BinanceErr::HTTP(e) if e.is_timeout() || e.is_request() || e.is_body() => {
+                parts.is_idempotent() || self.order_never_placed(parts, query).await
+            }
+            BinanceErr::ServerError { .. } => parts.is_idempotent() || self.order_never_placed(parts, query).await,
+            BinanceErr::BinanceContentError(e) => match e.error_code() {
+                // Rejected up front, a resend gets a fresh timestamp
+                BinanceErrorCode::TooManyRequests
+                | BinanceErrorCode::TooManyOrders
+                | BinanceErrorCode::ServerBusy
+                | BinanceErrorCode::InvalidTimestamp => true,
+                // "Send status unknown"
+                BinanceErrorCode::Unknown
+                | BinanceErrorCode::Disconnected
+                | BinanceErrorCode::UnexpectedResponse
+                | BinanceErrorCode::Timeout => parts.is_idempotent() || self.order_never_placed(parts, query).await,
+                _ => false
+            },
+            _ => false
+        };
+        if safe_to_resend {
+            Some(backoff)
+        } else {
+            None
+        }
+    }
+
+    /// After an ambiguous failure placing an order, look it up by its `newClientOrderId`.
+    /// Binance may still process the request until its `timestamp + recvWindow`, so the lookup
+    /// waits for that to pass: only an order unknown after that is safe to send again.
+    async fn order_never_placed(&self, parts: &RequestParts<'_>, query: &str) -> bool {
+        let query_endpoint = match order_query_endpoint(&parts.method, parts.endpoint) {
+            Some(query_endpoint) if parts.signed => query_endpoint,
+            _ => return false
+        };
+        let (symbol, client_order_id, timestamp) = match (
+            query_param(query, "symbol"),
+            query_param(query, "newClientOrderId"),
+            query_param(query, "timestamp").and_then(|t| t.parse::<u64>().ok()),
+        ) {
+            (Some(symbol), Some(client_order_id), Some(timestamp)) => (symbol, client_order_id, timestamp),
+            _ => return false
+        };
+        let recv_window = query_param(query, "recvWindow")
+            .and_then(|r| r.parse().ok())
+            .unwrap_or(DEFAULT_RECV_WINDOW);
+        let now = match self.time_sync.timestamp() {
+            Ok(now) => now,
+            Err(_) => return false
+        };
+        let expired_at = timestamp + recv_window;
+        tokio::time::sleep(Duration::from_millis(expired_at.saturating_sub(now)) + CLOCK_MARGIN).await;
+
+        let mut parameters: BTreeMap<String, String> = BTreeMap::new();
+        parameters.insert("symbol".into(), symbol.into());
+        parameters.insert("origClientOrderId".into(), client_order_id.into());
+        let request = match self.build_signed_request(parameters, recv_window) {
+            Ok(request) => request,
+            Err(_) => return false
+        };
+        let headers = match self.build_headers(true) {
+            Ok(headers) => headers,
+            Err(_) => return false
+        };
+        let lookup = RequestParts::signed(Method::GET, query_endpoint, &request, headers);
+        if self.rate_limiter.acquire(query_endpoint, request_weight(query_endpoint, &request), false).await.is_err() {
+            return false;
+        }
+        match self.send_once(&lookup, &request).await {
+            Err(BinanceErr::BinanceContentError(e)) => e.error_code() == BinanceErrorCode::NoSuchOrder,
+            _ => false
+        }
     }
 
-    async fn sign_request(&self, endpoint: &str, request: &str) -> String {
-        let mut signed_key = Hmac::<Sha256>::new_varkey(self.secret_key.as_bytes()).unwrap();
-        signed_key.update(request.as_bytes());
-        let signature = hex_encode(signed_key.finalize().into_bytes());
+    async fn sign_request(&self, endpoint: &str, request: &str) -> Result<String, BinanceErr> {
+        let signature = self.signer.sign(request).await?;
         let request_body: String = format!("{}&signature={}", request, signature);
         let url: String = format!("{}{}?{}", self.host, endpoint, request_body);
 
-        url
+        Ok(url)
     }
 
     fn build_headers(&self, content_type: bool) -> Result<HeaderMap, BinanceErr> {
         let mut custom_headers = HeaderMap::new();
 
-        custom_headers.insert(USER_AGENT, HeaderValue::from_static("binance-rs"));
         if content_type {
             custom_headers.insert(
                 CONTENT_TYPE,
@@ -141,26 +338,215 @@ impl Client {
         Ok(custom_headers)
     }
 
-    async fn handler(&self, response: Response) -> Result<String, BinanceErr> {
-        match response.status() {
+    fn handler(&self, endpoint: &str, response: TransportResponse) -> Result<String, BinanceErr> {
+        self.rate_limiter.update_from_headers(&response.headers);
+
+        match response.status {
             StatusCode::OK => {
-                Ok(response.text().await?)
-            }
-            StatusCode::INTERNAL_SERVER_ERROR => {
-                Err(BinanceErr::from_str(format!("Internal Server Error")))
+                Ok(response.body)
             }
-            StatusCode::SERVICE_UNAVAILABLE => {
-                Err(BinanceErr::from_str(format!("Service Unavailable")))
+            s if s.is_server_error() => {
+                Err(BinanceErr::ServerError {
+                    status: s.as_u16(),
+                    endpoint: endpoint.into(),
+                    msg: response.body,
+                })
             }
             StatusCode::UNAUTHORIZED => {
-                Err(BinanceErr::from_str(format!("Unauthorized")))
+                // Kept apart from the other 4xx whether or not Binance explains it with a code
+                let (code, msg) = match serde_json::from_str::<BinanceContentError>(&response.body) {
+                    Ok(e) => (Some(e.code), e.msg),
+                    Err(_) => (None, response.body),
+                };
+                Err(BinanceErr::Unauthorized { code, endpoint: endpoint.into(), msg })
             }
-            StatusCode::BAD_REQUEST => {
-                Err(BinanceErr::BinanceContentError(response.json::<BinanceContentError>().await?))
+            s @ StatusCode::TOO_MANY_REQUESTS | s @ StatusCode::IM_A_TEAPOT => {
+                let retry_after = response
+                    .headers
+                    .get(RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_RETRY_AFTER);
+                self.rate_limiter.back_off(retry_after);
+                Err(BinanceErr::RateLimited { retry_after, status: Some(s.as_u16()), endpoint: endpoint.into() })
             }
             s => {
-                Err(BinanceErr::from_str(format!("Received response: {:?}", s)))
+                // Binance explains most 4xx with a {"code": .., "msg": ..} body
+                let body = response.body;
+                match serde_json::from_str::<BinanceContentError>(&body) {
+                    Ok(mut e) => {
+                        e.status = s.as_u16();
+                        e.endpoint = endpoint.into();
+                        Err(BinanceErr::BinanceContentError(e))
+                    }
+                    Err(_) => Err(BinanceErr::UnexpectedStatus { status: s.as_u16(), endpoint: endpoint.into(), msg: body })
+                }
             }
         }
     }
 }
+
+/// A request as handed to `Client::send`, kept around so it can be rebuilt for a retry
+struct RequestParts<'a> {
+    method: Method,
+    endpoint: &'a str,
+    query: &'a str,
+    signed: bool,
+    headers: HeaderMap,
+    body: Option<String>,
+}
+
+impl<'a> RequestParts<'a> {
+    fn signed(method: Method, endpoint: &'a str, query: &'a str, headers: HeaderMap) -> Self {
+        RequestParts { method, endpoint, query, signed: true, headers, body: None }
+    }
+
+    fn unsigned(
+        method: Method, endpoint: &'a str, query: &'a str, headers: HeaderMap, body: Option<String>,
+    ) -> Self {
+        RequestParts { method, endpoint, query, signed: false, headers, body }
+    }
+
+    /// Safe to send twice: reads, listen key keepalives and test orders
+    fn is_idempotent(&self) -> bool {
+        self.method == Method::GET || self.method == Method::PUT || self.endpoint.ends_with("/test")
+    }
+}
+
+fn query_param<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query.split('&').find_map(|pair| {
+        let mut split = pair.splitn(2, '=');
+        match (split.next(), split.next()) {
+            (Some(key), Some(value)) if key == name => Some(value),
+            _ => None
+        }
+    })
+}
+
+/// Only new orders count towards the `ORDERS` limits, test orders, cancels and queries do not
+fn counts_as_order(method: &Method, endpoint: &str) -> bool {
+    *method == Method::POST && ORDER_ENDPOINTS.contains(&endpoint)
+}
+
+/// Where an order placed on `endpoint` is looked up by its client order id. Order lists and
+/// batches have no such lookup, they are never resent after an ambiguous failure.
+fn order_query_endpoint(method: &Method, endpoint: &str) -> Option<&'static str> {
+    match (method, endpoint) {
+        (&Method::POST, "/api/v3/order") => Some("/api/v3/order"),
+        (&Method::POST, "/fapi/v1/order") => Some("/fapi/v1/order"),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use reqwest::Method;
+
+    use crate::account::Account;
+    use crate::api::Binance;
+    use crate::error::{BinanceErr, BinanceErrorCode};
+    use crate::mock_server::MockBinanceServer;
+    use crate::numeric::Number;
+    use crate::order::{NewOrder, OrderSide};
+    use crate::retry::RetryPolicy;
+    use crate::transport::TransportRequest;
+
+    const SEND_STATUS_UNKNOWN: &str = r#"{"code":-1007,"msg":"Timeout waiting for response from backend server. Send status unknown; execution status unknown."}"#;
+
+    fn account(server: &MockBinanceServer) -> Account {
+        let retry_policy = RetryPolicy { initial_backoff: Duration::from_millis(10), jitter: false, ..RetryPolicy::default() };
+        let config = server.config().set_recv_window(200).set_retry_policy(retry_policy);
+        Account::new_with_config(Some("key".into()), Some("secret".into()), &config)
+    }
+
+    fn order(client_order_id: &str) -> NewOrder {
+        NewOrder::limit("BTCUSDT", OrderSide::Buy, Number::from(1), Number::from(100)).set_new_client_order_id(client_order_id)
+    }
+
+    fn sent(server: &MockBinanceServer, method: Method, path: &str) -> Vec<TransportRequest> {
+        server.requests().into_iter().filter(|r| r.method == method && r.path() == path).collect()
+    }
+
+    fn timestamp(request: &TransportRequest) -> u64 {
+        super::query_param(request.query(), "timestamp").unwrap().parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn an_order_found_after_a_timeout_is_not_sent_again() {
+        let server = MockBinanceServer::start().await.unwrap();
+        server.once(Method::POST, "/api/v3/order", 408, SEND_STATUS_UNKNOWN);
+        server.once(Method::GET, "/api/v3/order", 200, r#"{"symbol":"BTCUSDT","orderId":1,"clientOrderId":"placed-1","status":"NEW"}"#);
+
+        let error = account(&server).place_order(order("placed-1")).await.unwrap_err();
+        assert_eq!(error.error_code(), Some(BinanceErrorCode::Timeout));
+        let (orders, lookups) = (sent(&server, Method::POST, "/api/v3/order"), sent(&server, Method::GET, "/api/v3/order"));
+        assert_eq!((orders.len(), lookups.len()), (1, 1));
+        assert!(lookups[0].query().contains("origClientOrderId=placed-1"));
+        assert!(timestamp(&lookups[0]) > timestamp(&orders[0]) + 200, "looked up before the recvWindow was over");
+    }
+
+    #[tokio::test]
+    async fn an_order_unknown_after_a_timeout_is_sent_again() {
+        let server = MockBinanceServer::start().await.unwrap();
+        server.once(Method::POST, "/api/v3/order", 408, SEND_STATUS_UNKNOWN);
+
+        let transaction = account(&server).place_order(order("lost-1")).await.unwrap();
+        assert_eq!(transaction.client_order_id, "lost-1");
+        let (orders, lookups) = (sent(&server, Method::POST, "/api/v3/order"), sent(&server, Method::GET, "/api/v3/order"));
+        assert_eq!((orders.len(), lookups.len()), (2, 1));
+        assert!(timestamp(&orders[1]) > timestamp(&orders[0]) + 200);
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_not_retried_on_requests_that_change_state() {
+        let server = MockBinanceServer::start().await.unwrap();
+        let account = account(&server);
+        server.once(Method::DELETE, "/api/v3/order", 503, "Service Unavailable");
+        assert!(matches!(account.cancel_order("BTCUSDT", 1).await, Err(BinanceErr::ServerError { status: 503, .. })));
+        assert_eq!(sent(&server, Method::DELETE, "/api/v3/order").len(), 1);
+
+        // Without a client order id the order cannot be looked up
+        server.once(Method::POST, "/api/v3/order", 503, "Service Unavailable");
+        let anonymous = NewOrder::limit("BTCUSDT", OrderSide::Buy, Number::from(1), Number::from(100));
+        assert!(account.place_order(anonymous).await.is_err());
+        assert_eq!(sent(&server, Method::POST, "/api/v3/order").len(), 1);
+        assert!(sent(&server, Method::GET, "/api/v3/order").is_empty());
+
+        // Reads are resent
+        server.once(Method::GET, "/api/v3/account", 503, "Service Unavailable");
+        assert!(account.get_account().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn requests_outside_of_the_recv_window_are_resent_after_a_resync() {
+        let server = MockBinanceServer::start().await.unwrap();
+        let server_time = crate::util::get_timestamp().unwrap() + 60_000;
+        server.once(Method::POST, "/api/v3/order", 400, r#"{"code":-1021,"msg":"Timestamp for this request is outside of the recvWindow."}"#);
+        server.once(Method::GET, "/api/v3/time", 200, format!(r#"{{"serverTime":{}}}"#, server_time));
+
+        let account = account(&server);
+        account.place_order(order("skewed-1")).await.unwrap();
+        assert!(account.client.time_sync().offset().unwrap() > 50_000);
+        let orders = sent(&server, Method::POST, "/api/v3/order");
+        assert_eq!(orders.len(), 2);
+        assert!(timestamp(&orders[1]) >= server_time, "resent before the resync");
+        assert_eq!(sent(&server, Method::GET, "/api/v3/time").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn unauthorized_requests_are_reported_with_their_code() {
+        let server = MockBinanceServer::start().await.unwrap();
+        server.once(Method::GET, "/api/v3/account", 401, r#"{"code":-2015,"msg":"Invalid API-key, IP, or permissions for action."}"#);
+        let error = account(&server).get_account().await.unwrap_err();
+        assert!(error.is_unauthorized());
+        assert_eq!((error.status(), error.error_code()), (Some(401), Some(BinanceErrorCode::RejectedMbxKey)));
+
+        server.once(Method::GET, "/api/v3/account", 401, "Unauthorized");
+        let error = account(&server).get_account().await.unwrap_err();
+        assert!(matches!(&error, BinanceErr::Unauthorized { code: None, msg, .. } if msg == "Unauthorized"));
+        assert_eq!(error.endpoint(), Some("/api/v3/account"));
+    }
+}
@@ -0,0 +1,237 @@
+use std::time::Duration;
+
+use crate::rate_limit::RateLimitPolicy;
+use crate::retry::RetryPolicy;
+use crate::websocket::KeepAlive;
+
+/// Which Binance deployment a set of handles should talk to.
+///
+/// Every variant resolves to a spot REST host, a futures REST host (Binance.US has none)
+/// and a combined websocket stream url. `Custom` is meant for a local stand-in server,
+/// in which case the `rest` host serves both the spot and the futures routes.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum BinanceEnvironment {
+    #[default]
+    Production,
+    SpotTestnet,
+    FuturesTestnet,
+    BinanceUs,
+    Custom { rest: String, ws: String },
+}
+
+impl BinanceEnvironment {
+    pub fn rest_api_endpoint(&self) -> String {
+        match self {
+            BinanceEnvironment::Production => "https://api.binance.com".into(),
+            BinanceEnvironment::SpotTestnet => "https://testnet.binance.vision".into(),
+            BinanceEnvironment::FuturesTestnet => "https://testnet.binance.vision".into(),
+            BinanceEnvironment::BinanceUs => "https://api.binance.us".into(),
+            BinanceEnvironment::Custom { rest, .. } => rest.clone(),
+        }
+    }
+
+    /// `None` where there is no futures market, the futures handles then fail every request
+    pub fn futures_rest_api_endpoint(&self) -> Option<String> {
+        match self {
+            BinanceEnvironment::Production => Some("https://fapi.binance.com".into()),
+            BinanceEnvironment::SpotTestnet => Some("https://testnet.binancefuture.com".into()),
+            BinanceEnvironment::FuturesTestnet => Some("https://testnet.binancefuture.com".into()),
+            // Never fall back on the global exchange, it would get the Binance.US credentials
+            BinanceEnvironment::BinanceUs => None,
+            BinanceEnvironment::Custom { rest, .. } => Some(rest.clone()),
+        }
+    }
+
+    pub fn ws_endpoint(&self) -> String {
+        match self {
+            BinanceEnvironment::Production => "wss://stream.binance.com:9443/stream".into(),
+            BinanceEnvironment::SpotTestnet => "wss://testnet.binance.vision/stream".into(),
+            BinanceEnvironment::FuturesTestnet => "wss://stream.binancefuture.com/stream".into(),
+            BinanceEnvironment::BinanceUs => "wss://stream.binance.us:9443/stream".into(),
+            BinanceEnvironment::Custom { ws, .. } => ws.clone(),
+        }
+    }
+}
+
+/// Settings shared by every handle created through `Binance::new_with_config`.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub environment: BinanceEnvironment,
+    pub rest_api_endpoint: String,
+    pub futures_rest_api_endpoint: Option<String>,
+    pub ws_endpoint: String,
+    pub recv_window: u64,
+
+    // HTTP settings, applied to the one pooled client every REST handle shares
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    pub pool_idle_timeout: Option<Duration>,
+    pub tcp_keepalive: Option<Duration>,
+    pub proxy: Option<reqwest::Proxy>,
+    pub user_agent: String,
+
+    pub rate_limit_policy: RateLimitPolicy,
+    pub retry_policy: RetryPolicy,
+    /// Websocket reconnection after a dropped connection, `max_attempts` counting the attempts per outage
+    pub reconnect_policy: RetryPolicy,
+    pub keepalive: KeepAlive,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::from_environment(BinanceEnvironment::default())
+    }
+}
+
+impl Config {
+    pub fn from_environment(environment: BinanceEnvironment) -> Self {
+        Config {
+            rest_api_endpoint: environment.rest_api_endpoint(),
+            futures_rest_api_endpoint: environment.futures_rest_api_endpoint(),
+            ws_endpoint: environment.ws_endpoint(),
+            environment,
+            recv_window: 5000,
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            tcp_keepalive: Some(Duration::from_secs(60)),
+            proxy: None,
+            user_agent: concat!("binance_api_async/", env!("CARGO_PKG_VERSION")).into(),
+            rate_limit_policy: RateLimitPolicy::default(),
+            retry_policy: RetryPolicy::default(),
+            reconnect_policy: RetryPolicy::reconnect(),
+            keepalive: KeepAlive::default(),
+        }
+    }
+
+    /// Switch environment, resetting all endpoints to the ones it defines
+    pub fn set_environment(self, environment: BinanceEnvironment) -> Self {
+        Config {
+            rest_api_endpoint: environment.rest_api_endpoint(),
+            futures_rest_api_endpoint: environment.futures_rest_api_endpoint(),
+            ws_endpoint: environment.ws_endpoint(),
+            environment,
+            ..self
+        }
+    }
+
+    pub fn set_rest_api_endpoint<S: Into<String>>(mut self, rest_api_endpoint: S) -> Self {
+        self.rest_api_endpoint = rest_api_endpoint.into();
+        self
+    }
+
+    pub fn set_futures_rest_api_endpoint<S: Into<String>>(mut self, futures_rest_api_endpoint: S) -> Self {
+        self.futures_rest_api_endpoint = Some(futures_rest_api_endpoint.into());
+        self
+    }
+
+    pub fn set_ws_endpoint<S: Into<String>>(mut self, ws_endpoint: S) -> Self {
+        self.ws_endpoint = ws_endpoint.into();
+        self
+    }
+
+    pub fn set_recv_window(mut self, recv_window: u64) -> Self {
+        self.recv_window = recv_window;
+        self
+    }
+
+    /// Total time allowed for a request, from connecting to reading the whole body
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn set_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    /// How long an idle pooled connection is kept alive, `None` keeps it forever
+    pub fn set_pool_idle_timeout(mut self, pool_idle_timeout: Option<Duration>) -> Self {
+        self.pool_idle_timeout = pool_idle_timeout;
+        self
+    }
+
+    pub fn set_tcp_keepalive(mut self, tcp_keepalive: Option<Duration>) -> Self {
+        self.tcp_keepalive = tcp_keepalive;
+        self
+    }
+
+    pub fn set_proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    pub fn set_user_agent<S: Into<String>>(mut self, user_agent: S) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// What to do when a request would exceed the request weight or order limits
+    pub fn set_rate_limit_policy(mut self, rate_limit_policy: RateLimitPolicy) -> Self {
+        self.rate_limit_policy = rate_limit_policy;
+        self
+    }
+
+    /// Use `RetryPolicy::none()` to never resend a failed request
+    pub fn set_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// How websocket subscriptions reconnect when their connection drops
+    pub fn set_reconnect_policy(mut self, reconnect_policy: RetryPolicy) -> Self {
+        self.reconnect_policy = reconnect_policy;
+        self
+    }
+
+    /// Websocket pings, and the silence after which a connection is reconnected
+    pub fn set_keepalive(mut self, keepalive: KeepAlive) -> Self {
+        self.keepalive = keepalive;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use reqwest::Method;
+
+    use crate::api::Binance;
+    use crate::binance_futures::general::FuturesGeneral;
+    use crate::client::Client;
+    use crate::config::*;
+    use crate::transport::MockTransport;
+
+    #[test]
+    fn environments_resolve_their_endpoints() {
+        let environments = [
+            (BinanceEnvironment::Production, "https://api.binance.com", Some("https://fapi.binance.com"), "wss://stream.binance.com:9443/stream"),
+            (BinanceEnvironment::SpotTestnet, "https://testnet.binance.vision", Some("https://testnet.binancefuture.com"), "wss://testnet.binance.vision/stream"),
+            (BinanceEnvironment::FuturesTestnet, "https://testnet.binance.vision", Some("https://testnet.binancefuture.com"), "wss://stream.binancefuture.com/stream"),
+            (BinanceEnvironment::BinanceUs, "https://api.binance.us", None, "wss://stream.binance.us:9443/stream"),
+            (BinanceEnvironment::Custom { rest: "http://127.0.0.1:8080".into(), ws: "ws://127.0.0.1:8081/stream".into() },
+                "http://127.0.0.1:8080", Some("http://127.0.0.1:8080"), "ws://127.0.0.1:8081/stream"),
+        ];
+        for (environment, rest, futures, ws) in environments {
+            let config = Config::default().set_environment(environment.clone());
+            assert_eq!(config.rest_api_endpoint, rest, "{:?}", environment);
+            assert_eq!(config.futures_rest_api_endpoint.as_deref(), futures, "{:?}", environment);
+            assert_eq!(config.ws_endpoint, ws, "{:?}", environment);
+        }
+        assert_eq!(Config::default().environment, BinanceEnvironment::Production);
+    }
+
+    #[tokio::test]
+    async fn futures_handles_without_a_futures_market_send_nothing() {
+        let mock = Arc::new(MockTransport::new());
+        mock.on(Method::GET, "/fapi/v1/ping", 200, "{}");
+        let config = Config::from_environment(BinanceEnvironment::BinanceUs);
+        let client = Client::new_with_config(None, None, config.rest_api_endpoint.clone(), &config).with_transport(mock.clone());
+
+        let general = FuturesGeneral::new_with_client(&client, &config);
+        assert!(general.ping().await.is_err());
+        assert!(mock.requests().is_empty());
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use futures::{SinkExt, StreamExt};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tokio_tungstenite::WebSocketStream;
+use url::Url;
+use uuid::Uuid;
+
+use crate::error::BinanceErr;
+use crate::error::other_err::BinanceContentError;
+use crate::retry::RetryPolicy;
+use crate::userstream::ListenKeyEvent;
+use crate::websocket::{ExchangeSettings, KeepAlive};
+
+type WSStream = WebSocketStream<tokio_tungstenite::stream::Stream<TcpStream, tokio_native_tls::TlsStream<TcpStream>>>;
+
+pub(crate) type Ack = oneshot::Sender<Result<Value, BinanceErr>>;
+
+/// A method frame waiting for its response
+enum Pending {
+    Command(Ack),
+    /// The SUBSCRIBE sent after reconnecting, the subscriptions learn the outcome
+    Resubscribe,
+}
+
+/// Sent by the `Websocket` to the task of one of its connections
+pub(crate) enum Command {
+    /// Route `streams` to `subscription`, subscribing to the ones the connection does not carry yet
+    Subscribe { subscription: Uuid, streams: Vec<String>, ack: Ack },
+    /// Stop routing `streams` to `subscription`, unsubscribing from the ones nobody else uses
+    Unsubscribe { subscription: Uuid, streams: Vec<String>, ack: Ack },
+    ListSubscriptions { ack: Ack },
+}
+
+/// What a connection task reports about one of the subscriptions it carries
+#[derive(Clone)]
+pub(crate) enum ConnectionEvent {
+    Message(Message),
+    Disconnected(String),
+    Reconnected,
+    /// Reconnecting was given up, the subscription is over
+    Failed(String),
+    /// Reported by the listen key manager of a user data stream rather than the connection
+    ListenKey(ListenKeyEvent),
+}
+
+/// Consumers of each subscription, every one of them gets every event
+pub(crate) type Subscribers = Arc<Mutex<HashMap<Uuid, Vec<mpsc::UnboundedSender<ConnectionEvent>>>>>;
+
+/// When each subscription last received a message, shared by the `Websocket` and its connections
+pub(crate) type LastMessages = Arc<Mutex<HashMap<Uuid, Instant>>>;
+
+/// One websocket connection to the combined stream endpoint, shared by any number of
+/// subscriptions. Reconnects and subscribes to every stream again when it drops, or when
+/// nothing at all (pongs included) was received for `KeepAlive::stale_after`.
+pub(crate) struct Connection {
+    url: Url,
+    /// Streams named in the url (listen keys), subscribed to without a SUBSCRIBE frame
+    url_streams: HashSet<String>,
+    routes: HashMap<String, HashSet<Uuid>>,
+    pending: HashMap<u64, Pending>,
+    next_request_id: u64,
+    reconnect_policy: RetryPolicy,
+    keepalive: KeepAlive,
+    subscribers: Subscribers,
+    last_messages: LastMessages,
+}
+
+impl Connection {
+    /// Connect to `url`, whose `streams` parameter is routed to `subscription` if there is one
+    pub(crate) async fn open(
+        url: Url, subscription: Option<Uuid>, reconnect_policy: RetryPolicy, keepalive: KeepAlive,
+        subscribers: Subscribers, last_messages: LastMessages,
+    ) -> Result<(Connection, WSStream), BinanceErr> {
+        let ws_stream = connect(&url).await?;
+        let url_streams: HashSet<String> = url
+            .query_pairs()
+            .find(|(key, _)| key == "streams")
+            .map(|(_, streams)| streams.split('/').map(String::from).collect())
+            .unwrap_or_default();
+        let routes = match subscription {
+            Some(subscription) => url_streams.iter().map(|s| (s.clone(), std::iter::once(subscription).collect())).collect(),
+            None => HashMap::new()
+        };
+        let connection = Connection {
+            url,
+            url_streams,
+            routes,
+            pending: HashMap::new(),
+            next_request_id: 1,
+            reconnect_policy,
+            keepalive,
+            subscribers,
+            last_messages,
+        };
+        Ok((connection, ws_stream))
+    }
+
+    pub(crate) async fn run(mut self, mut ws_stream: WSStream, mut commands: mpsc::UnboundedReceiver<Command>) {
+        loop {
+            let ping_interval = self.keepalive.ping_interval;
+            let mut ping = tokio::time::interval_at(tokio::time::Instant::now() + ping_interval, ping_interval);
+            let mut last_received = tokio::time::Instant::now();
+            let reason = loop {
+                tokio::select! {
+                    message = ws_stream.next() => {
+                        last_received = tokio::time::Instant::now();
+                        match message {
+                            Some(Ok(Message::Text(text))) => {
+                                if let Err(e) = self.receive(text) {
+                                    ws_stream.close(None).await.ok();
+                                    return self.give_up(e.to_string());
+                                }
+                            }
+                            // Binance closes the connection when pings go unanswered
+                            Some(Ok(Message::Ping(payload))) => {
+                                if let Err(e) = ws_stream.send(Message::Pong(payload)).await {
+                                    break e.to_string();
+                                }
+                            }
+                            Some(Ok(Message::Close(frame))) => {
+                                break frame.map_or_else(|| "Closed by the server".into(), |frame| frame.reason.into_owned())
+                            }
+                            Some(Ok(_)) => {}
+                            Some(Err(e)) => break e.to_string(),
+                            None => break "Connection closed".into(),
+                        }
+                    },
+                    _ = ping.tick() => {
+                        if let Err(e) = ws_stream.send(Message::Ping(Vec::new())).await {
+                            break e.to_string();
+                        }
+                    },
+                    _ = tokio::time::sleep_until(last_received + self.keepalive.stale_after) => {
+                        break format!("Nothing received for {:?}", self.keepalive.stale_after);
+                    },
+                    command = commands.recv() => match command {
+                        Some(command) => {
+                            if let Err(e) = self.execute(&mut ws_stream, command).await {
+                                break e.to_string();
+                            }
+                        }
+                        // The `Websocket` let go of the connection
+                        None => {
+                            ws_stream.close(None).await.ok();
+                            return;
+                        }
+                    },
+                }
+            };
+
+            for (_, pending) in self.pending.drain() {
+                if let Pending::Command(ack) = pending {
+                    ack.send(Err(BinanceErr::from_str(format!("Websocket disconnected: {}", reason)))).ok();
+                }
+            }
+            self.report_all(|| ConnectionEvent::Disconnected(reason.clone()));
+            ws_stream = match self.reconnect().await {
+                Ok(ws_stream) => ws_stream,
+                Err(e) => return self.give_up(e.to_string()),
+            };
+        }
+    }
+
+    /// Reconnecting failed for good: the subscriptions are told and forgotten
+    fn give_up(&mut self, reason: String) {
+        self.report_all(|| ConnectionEvent::Failed(reason.clone()));
+        let subscriptions = self.subscriptions();
+        self.lock_subscribers().retain(|subscription, _| !subscriptions.contains(subscription));
+    }
+
+    async fn execute(&mut self, ws_stream: &mut WSStream, command: Command) -> Result<(), BinanceErr> {
+        match command {
+            Command::Subscribe { subscription, streams, ack } => {
+                let new_streams: Vec<String> = streams.iter().filter(|s| !self.routes.contains_key(*s)).cloned().collect();
+                for stream in streams {
+                    self.routes.entry(stream).or_default().insert(subscription);
+                }
+                self.request(ws_stream, "SUBSCRIBE", new_streams, ack).await
+            }
+            Command::Unsubscribe { subscription, streams, ack } => {
+                let mut unused_streams = Vec::new();
+                for stream in streams {
+                    let unused = self.routes.get_mut(&stream).is_some_and(|subscriptions| {
+                        subscriptions.remove(&subscription);
+                        subscriptions.is_empty()
+                    });
+                    if unused {
+                        self.routes.remove(&stream);
+                        if !self.url_streams.contains(&stream) {
+                            unused_streams.push(stream);
+                        }
+                    }
+                }
+                self.request(ws_stream, "UNSUBSCRIBE", unused_streams, ack).await
+            }
+            Command::ListSubscriptions { ack } => self.request(ws_stream, "LIST_SUBSCRIPTIONS", Vec::new(), ack).await,
+        }
+    }
+
+    /// Send a method frame with a fresh id, `ack` is answered when the response with that id arrives
+    async fn request(&mut self, ws_stream: &mut WSStream, method: &'static str, params: Vec<String>, ack: Ack) -> Result<(), BinanceErr> {
+        if params.is_empty() && method != "LIST_SUBSCRIPTIONS" {
+            ack.send(Ok(Value::Null)).ok();
+            return Ok(());
+        }
+        let id = self.next_request_id;
+        self.next_request_id += 1;
+        self.pending.insert(id, Pending::Command(ack));
+        ws_stream.send(Message::Text(method_frame(method, params, id))).await?;
+        Ok(())
+    }
+
+    /// Route a received text frame to the subscriptions of its stream.
+    /// Fails when the streams could not be subscribed to again after reconnecting.
+    fn receive(&mut self, text: String) -> Result<(), BinanceErr> {
+        let value: Value = match serde_json::from_str(&text) {
+            Ok(value) => value,
+            // Not ours to judge, the subscriptions report it while parsing
+            Err(_) => {
+                self.report_all(|| ConnectionEvent::Message(Message::Text(text.clone())));
+                return Ok(());
+            }
+        };
+
+        if let Some(id) = value.get("id").and_then(Value::as_u64) {
+            let response = match value.get("code") {
+                Some(_) => Err(serde_json::from_value::<BinanceContentError>(value).map_or_else(BinanceErr::from, BinanceErr::from)),
+                None => Ok(value["result"].clone())
+            };
+            match (self.pending.remove(&id), response) {
+                (Some(Pending::Command(ack)), response) => {
+                    ack.send(response).ok();
+                }
+                (Some(Pending::Resubscribe), Ok(_)) => self.report_all(|| ConnectionEvent::Reconnected),
+                (Some(Pending::Resubscribe), Err(e)) => return Err(e),
+                (None, _) => {}
+            }
+            return Ok(());
+        }
+
+        let subscriptions = match value.get("stream").and_then(Value::as_str) {
+            Some(stream) => self.routes.get(stream).cloned().unwrap_or_default(),
+            None => self.subscriptions(),
+        };
+        let now = Instant::now();
+        let mut last_messages = self.last_messages.lock().unwrap_or_else(|e| e.into_inner());
+        last_messages.extend(subscriptions.iter().map(|subscription| (*subscription, now)));
+        drop(last_messages);
+        for subscription in subscriptions {
+            self.report(subscription, ConnectionEvent::Message(Message::Text(text.clone())));
+        }
+        Ok(())
+    }
+
+    async fn reconnect(&mut self) -> Result<WSStream, BinanceErr> {
+        let mut attempt = 1;
+        loop {
+            tokio::time::sleep(self.reconnect_policy.backoff(attempt)).await;
+            match self.resubscribe().await {
+                Ok(ws_stream) => return Ok(ws_stream),
+                Err(e) if attempt >= self.reconnect_policy.max_attempts => return Err(e),
+                Err(_) => attempt += 1,
+            }
+        }
+    }
+
+    /// Connect again and subscribe to every stream. `Reconnected` is reported once Binance
+    /// acknowledges the SUBSCRIBE, or right away when the url names every stream.
+    async fn resubscribe(&mut self) -> Result<WSStream, BinanceErr> {
+        let mut ws_stream = connect(&self.url).await?;
+        let streams: Vec<String> = self.routes.keys().filter(|s| !self.url_streams.contains(*s)).cloned().collect();
+        if streams.is_empty() {
+            self.report_all(|| ConnectionEvent::Reconnected);
+        } else {
+            let id = self.next_request_id;
+            self.next_request_id += 1;
+            ws_stream.send(Message::Text(method_frame("SUBSCRIBE", streams, id))).await?;
+            self.pending.insert(id, Pending::Resubscribe);
+        }
+        Ok(ws_stream)
+    }
+
+    fn subscriptions(&self) -> HashSet<Uuid> {
+        self.routes.values().flatten().copied().collect()
+    }
+
+    /// Consumers that went away are forgotten
+    fn report(&self, subscription: Uuid, event: ConnectionEvent) {
+        if let Some(senders) = self.lock_subscribers().get_mut(&subscription) {
+            senders.retain(|sender| sender.send(event.clone()).is_ok());
+        }
+    }
+
+    fn report_all<F: Fn() -> ConnectionEvent>(&self, event: F) {
+        for subscription in self.subscriptions() {
+            self.report(subscription, event());
+        }
+    }
+
+    fn lock_subscribers(&self) -> std::sync::MutexGuard<'_, HashMap<Uuid, Vec<mpsc::UnboundedSender<ConnectionEvent>>>> {
+        self.subscribers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+async fn connect(url: &Url) -> Result<WSStream, BinanceErr> {
+    let (ws_stream, _) = connect_async(url.clone()).await?;
+    Ok(ws_stream)
+}
+
+fn method_frame(method: &'static str, params: Vec<String>, id: u64) -> String {
+    let frame = ExchangeSettings::new(method, params, id);
+    serde_json::to_string(&frame).unwrap_or_default()
+}
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use crate::error::other_err::{BinanceContentError, BinanceMiscError};
 
 pub mod other_err {
@@ -6,16 +8,51 @@ pub mod other_err {
     use serde::*;
     use serde_json::Value;
 
+    use crate::error::{BinanceErrorCode, FilterFailure, RejectReason};
+
     #[derive(thiserror::Error, Debug, Serialize, Deserialize)]
-    #[error("({:?}) {:?}\n{:?}", code, msg, extra)]
+    #[error("({:?}) {:?} [{} {}]\n{:?}", code, msg, status, endpoint, extra)]
     pub struct BinanceContentError {
         pub code: i16,
         pub msg: String,
 
+        /// HTTP status the error came with, filled in by the client
+        #[serde(skip)]
+        pub status: u16,
+        /// Endpoint the failed request was sent to, filled in by the client
+        #[serde(skip)]
+        pub endpoint: String,
+
         #[serde(flatten)]
         pub extra: HashMap<String, Value>,
     }
 
+    impl BinanceContentError {
+        pub fn error_code(&self) -> BinanceErrorCode {
+            BinanceErrorCode::from(self.code)
+        }
+
+        /// The filter an order failed, from messages like "Filter failure: LOT_SIZE"
+        pub fn filter_failure(&self) -> Option<FilterFailure> {
+            self.msg
+                .strip_prefix("Filter failure: ")
+                .map(|filter| FilterFailure::from(filter.trim()))
+        }
+
+        /// Why a new order (-2010) or a cancel (-2011) was rejected
+        pub fn reject_reason(&self) -> Option<RejectReason> {
+            match self.error_code() {
+                BinanceErrorCode::NewOrderRejected | BinanceErrorCode::CancelRejected => Some(RejectReason::from(self.msg.as_str())),
+                _ => None
+            }
+        }
+
+        /// -2010 "Account has insufficient balance for requested action."
+        pub fn is_insufficient_balance(&self) -> bool {
+            self.reject_reason() == Some(RejectReason::InsufficientBalance)
+        }
+    }
+
     #[derive(thiserror::Error, Debug)]
     #[error("{:?}", msg)]
     pub struct BinanceMiscError {
@@ -29,28 +66,282 @@ pub mod other_err {
     }
 }
 
+/// Error codes from https://github.com/binance/binance-spot-api-docs/blob/master/errors.md
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinanceErrorCode {
+    // 10xx - General Server or Network issues
+    Unknown,
+    Disconnected,
+    Unauthorized,
+    TooManyRequests,
+    UnexpectedResponse,
+    Timeout,
+    ServerBusy,
+    /// Also the code of every "Filter failure: ..." rejection
+    InvalidMessage,
+    UnknownOrderComposition,
+    TooManyOrders,
+    ServiceShuttingDown,
+    UnsupportedOperation,
+    /// "Timestamp for this request is outside of the recvWindow."
+    InvalidTimestamp,
+    InvalidSignature,
+
+    // 11xx - Request issues
+    IllegalChars,
+    TooManyParameters,
+    MandatoryParamEmptyOrMalformed,
+    UnknownParam,
+    UnreadParameters,
+    ParamEmpty,
+    ParamNotRequired,
+    BadPrecision,
+    NoDepth,
+    TifNotRequired,
+    InvalidTif,
+    InvalidOrderType,
+    InvalidSide,
+    EmptyNewClientOrderId,
+    EmptyOrigClientOrderId,
+    BadInterval,
+    BadSymbol,
+    InvalidListenKey,
+    MoreThanXxHours,
+    OptionalParamsBadCombo,
+    InvalidParameter,
+
+    // 20xx - Processing issues
+    NewOrderRejected,
+    CancelRejected,
+    NoSuchOrder,
+    BadApiKeyFormat,
+    RejectedMbxKey,
+    NoTradingWindow,
+    OrderArchived,
+
+    Other(i16),
+}
+
+impl From<i16> for BinanceErrorCode {
+    fn from(code: i16) -> Self {
+        match code {
+            -1000 => BinanceErrorCode::Unknown,
+            -1001 => BinanceErrorCode::Disconnected,
+            -1002 => BinanceErrorCode::Unauthorized,
+            -1003 => BinanceErrorCode::TooManyRequests,
+            -1006 => BinanceErrorCode::UnexpectedResponse,
+            -1007 => BinanceErrorCode::Timeout,
+            -1008 => BinanceErrorCode::ServerBusy,
+            -1013 => BinanceErrorCode::InvalidMessage,
+            -1014 => BinanceErrorCode::UnknownOrderComposition,
+            -1015 => BinanceErrorCode::TooManyOrders,
+            -1016 => BinanceErrorCode::ServiceShuttingDown,
+            -1020 => BinanceErrorCode::UnsupportedOperation,
+            -1021 => BinanceErrorCode::InvalidTimestamp,
+            -1022 => BinanceErrorCode::InvalidSignature,
+            -1100 => BinanceErrorCode::IllegalChars,
+            -1101 => BinanceErrorCode::TooManyParameters,
+            -1102 => BinanceErrorCode::MandatoryParamEmptyOrMalformed,
+            -1103 => BinanceErrorCode::UnknownParam,
+            -1104 => BinanceErrorCode::UnreadParameters,
+            -1105 => BinanceErrorCode::ParamEmpty,
+            -1106 => BinanceErrorCode::ParamNotRequired,
+            -1111 => BinanceErrorCode::BadPrecision,
+            -1112 => BinanceErrorCode::NoDepth,
+            -1114 => BinanceErrorCode::TifNotRequired,
+            -1115 => BinanceErrorCode::InvalidTif,
+            -1116 => BinanceErrorCode::InvalidOrderType,
+            -1117 => BinanceErrorCode::InvalidSide,
+            -1118 => BinanceErrorCode::EmptyNewClientOrderId,
+            -1119 => BinanceErrorCode::EmptyOrigClientOrderId,
+            -1120 => BinanceErrorCode::BadInterval,
+            -1121 => BinanceErrorCode::BadSymbol,
+            -1125 => BinanceErrorCode::InvalidListenKey,
+            -1127 => BinanceErrorCode::MoreThanXxHours,
+            -1128 => BinanceErrorCode::OptionalParamsBadCombo,
+            -1130 => BinanceErrorCode::InvalidParameter,
+            -2010 => BinanceErrorCode::NewOrderRejected,
+            -2011 => BinanceErrorCode::CancelRejected,
+            -2013 => BinanceErrorCode::NoSuchOrder,
+            -2014 => BinanceErrorCode::BadApiKeyFormat,
+            -2015 => BinanceErrorCode::RejectedMbxKey,
+            -2016 => BinanceErrorCode::NoTradingWindow,
+            -2026 => BinanceErrorCode::OrderArchived,
+            code => BinanceErrorCode::Other(code),
+        }
+    }
+}
+
+impl BinanceErrorCode {
+    /// Failures of the exchange rather than of the request, worth sending again
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            BinanceErrorCode::Unknown
+                | BinanceErrorCode::Disconnected
+                | BinanceErrorCode::TooManyRequests
+                | BinanceErrorCode::UnexpectedResponse
+                | BinanceErrorCode::Timeout
+                | BinanceErrorCode::ServerBusy
+                | BinanceErrorCode::TooManyOrders
+                | BinanceErrorCode::InvalidTimestamp
+        )
+    }
+}
+
+/// Symbol or exchange filter named in a "Filter failure: ..." rejection
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FilterFailure {
+    PriceFilter,
+    PercentPrice,
+    PercentPriceBySide,
+    LotSize,
+    MinNotional,
+    Notional,
+    IcebergParts,
+    MarketLotSize,
+    MaxPosition,
+    MaxNumOrders,
+    MaxNumAlgoOrders,
+    MaxNumIcebergOrders,
+    TrailingDelta,
+    ExchangeMaxNumOrders,
+    ExchangeMaxNumAlgoOrders,
+    Other(String),
+}
+
+impl From<&str> for FilterFailure {
+    fn from(filter: &str) -> Self {
+        match filter {
+            "PRICE_FILTER" => FilterFailure::PriceFilter,
+            "PERCENT_PRICE" => FilterFailure::PercentPrice,
+            "PERCENT_PRICE_BY_SIDE" => FilterFailure::PercentPriceBySide,
+            "LOT_SIZE" => FilterFailure::LotSize,
+            "MIN_NOTIONAL" => FilterFailure::MinNotional,
+            "NOTIONAL" => FilterFailure::Notional,
+            "ICEBERG_PARTS" => FilterFailure::IcebergParts,
+            "MARKET_LOT_SIZE" => FilterFailure::MarketLotSize,
+            "MAX_POSITION" => FilterFailure::MaxPosition,
+            "MAX_NUM_ORDERS" => FilterFailure::MaxNumOrders,
+            "MAX_NUM_ALGO_ORDERS" => FilterFailure::MaxNumAlgoOrders,
+            "MAX_NUM_ICEBERG_ORDERS" => FilterFailure::MaxNumIcebergOrders,
+            "TRAILING_DELTA" => FilterFailure::TrailingDelta,
+            "EXCHANGE_MAX_NUM_ORDERS" => FilterFailure::ExchangeMaxNumOrders,
+            "EXCHANGE_MAX_NUM_ALGO_ORDERS" => FilterFailure::ExchangeMaxNumAlgoOrders,
+            other => FilterFailure::Other(other.to_string()),
+        }
+    }
+}
+
+/// Message of a -2010 `NEW_ORDER_REJECTED` or -2011 `CANCEL_REJECTED`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RejectReason {
+    InsufficientBalance,
+    UnknownOrder,
+    DuplicateOrder,
+    MarketClosed,
+    PriceQtyExceedHardLimits,
+    UnsupportedOrderCombination,
+    /// A LIMIT_MAKER order that would have been a taker
+    WouldImmediatelyMatch,
+    /// A stop order whose stop price is already reached
+    WouldTriggerImmediately,
+    OrderCancelReplacePartiallyFailed,
+    Other(String),
+}
+
+impl From<&str> for RejectReason {
+    fn from(msg: &str) -> Self {
+        match msg.trim_end_matches('.') {
+            "Account has insufficient balance for requested action" => RejectReason::InsufficientBalance,
+            "Unknown order sent" => RejectReason::UnknownOrder,
+            "Duplicate order sent" => RejectReason::DuplicateOrder,
+            "Market is closed" => RejectReason::MarketClosed,
+            "Price or QTY exceed the hard limits" => RejectReason::PriceQtyExceedHardLimits,
+            "Unsupported order combination" => RejectReason::UnsupportedOrderCombination,
+            "Order would immediately match and take" => RejectReason::WouldImmediatelyMatch,
+            "Order would trigger immediately" => RejectReason::WouldTriggerImmediately,
+            "Order cancel-replace partially failed" => RejectReason::OrderCancelReplacePartiallyFailed,
+            _ => RejectReason::Other(msg.to_string()),
+        }
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
-#[error(transparent)]
 pub enum BinanceErr {
+    #[error(transparent)]
     Serde(#[from] serde_json::error::Error),
 
-    Websocket(#[from] tokio_tungstenite::tungstenite::Error),
+    /// Boxed, it would otherwise make every `Result<_, BinanceErr>` three times as large
+    #[error(transparent)]
+    Websocket(Box<tokio_tungstenite::tungstenite::Error>),
 
+    #[error(transparent)]
     InvalidHeaderValue(#[from] reqwest::header::InvalidHeaderValue),
 
+    #[error(transparent)]
     HTTP(#[from] reqwest::Error),
 
+    #[error(transparent)]
     IoError(#[from] std::io::Error),
 
+    #[error(transparent)]
     SystemTimeError(#[from] std::time::SystemTimeError),
 
+    #[error(transparent)]
     ParseFloatError(#[from] std::num::ParseFloatError),
 
+    /// Binance answered with a `{"code": .., "msg": ..}` error body
+    #[error(transparent)]
     BinanceContentError(#[from] BinanceContentError),
 
+    /// Held back by the client side rate limiter (no status), or banned by Binance (429/418)
+    #[error("Rate limited on {}, retry after {:?}", endpoint, retry_after)]
+    RateLimited { retry_after: Duration, status: Option<u16>, endpoint: String },
+
+    /// 5xx, the request may or may not have been executed
+    #[error("Server error ({}) on {}: {}", status, endpoint, msg)]
+    ServerError { status: u16, endpoint: String, msg: String },
+
+    /// 401, the API key is missing, invalid or lacks the permission for the endpoint
+    #[error("Unauthorized on {}: {}", endpoint, msg)]
+    Unauthorized { code: Option<i16>, endpoint: String, msg: String },
+
+    /// Any other unsuccessful status that came without a Binance error body
+    #[error("Received response ({}) on {}: {}", status, endpoint, msg)]
+    UnexpectedStatus { status: u16, endpoint: String, msg: String },
+
+    /// Loading a signing key or producing a request signature failed
+    #[error("Signer error: {0}")]
+    Signer(String),
+
+    /// Rejected locally before sending, the order could never have been accepted
+    #[error("Invalid order: {0}")]
+    InvalidOrder(String),
+
+    /// Rejected locally by `NewOrder::apply_filters`, Binance would have answered "Filter failure: .."
+    #[error("Filter failure {:?}: {}", filter, msg)]
+    FilterViolation { filter: FilterFailure, msg: String },
+
+    /// The symbol is not listed in the exchange information
+    #[error("Unknown symbol: {0}")]
+    UnknownSymbol(String),
+
+    /// A REST or websocket payload without the expected shape, kept as received.
+    /// `endpoint` is set when it was the body of a REST response.
+    #[error("Malformed payload ({}): {}", msg, payload)]
+    MalformedPayload { msg: String, payload: String, endpoint: Option<String> },
+
+    #[error(transparent)]
     Other(#[from] BinanceMiscError),
 }
 
+impl From<tokio_tungstenite::tungstenite::Error> for BinanceErr {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        BinanceErr::Websocket(Box::new(e))
+    }
+}
+
 impl BinanceErr {
     pub fn from_str(msg: String) -> Self {
         BinanceErr::Other(BinanceMiscError::from(msg))
@@ -65,7 +356,184 @@ impl BinanceErr {
             BinanceErr::SystemTimeError(e) => format!("{}", e),
             BinanceErr::ParseFloatError(e) => format!("{}", e),
             BinanceErr::BinanceContentError(e) => format!("{}", e),
+            BinanceErr::RateLimited { .. }
+            | BinanceErr::ServerError { .. }
+            | BinanceErr::Unauthorized { .. }
+            | BinanceErr::UnexpectedStatus { .. }
+            | BinanceErr::Signer(_)
+            | BinanceErr::InvalidOrder(_)
+            | BinanceErr::FilterViolation { .. }
+            | BinanceErr::UnknownSymbol(_)
+            | BinanceErr::MalformedPayload { .. } => format!("{}", self),
             BinanceErr::Other(e) => format!("{}", e),
         }
     }
+
+    /// The Binance error code, when the error came with one
+    pub fn error_code(&self) -> Option<BinanceErrorCode> {
+        match self {
+            BinanceErr::BinanceContentError(e) => Some(e.error_code()),
+            BinanceErr::Unauthorized { code, .. } => code.map(BinanceErrorCode::from),
+            _ => None
+        }
+    }
+
+    /// HTTP status of the response that caused the error, `None` for errors raised locally
+    /// and for successful responses whose body did not parse
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            BinanceErr::HTTP(e) => e.status().map(|s| s.as_u16()),
+            BinanceErr::BinanceContentError(e) => Some(e.status),
+            BinanceErr::RateLimited { status, .. } => *status,
+            BinanceErr::Unauthorized { .. } => Some(401),
+            BinanceErr::ServerError { status, .. } | BinanceErr::UnexpectedStatus { status, .. } => Some(*status),
+            _ => None
+        }
+    }
+
+    /// Endpoint of the request that caused the error
+    pub fn endpoint(&self) -> Option<&str> {
+        match self {
+            BinanceErr::HTTP(e) => e.url().map(|url| url.path()),
+            BinanceErr::BinanceContentError(e) => Some(e.endpoint.as_str()),
+            BinanceErr::RateLimited { endpoint, .. }
+            | BinanceErr::ServerError { endpoint, .. }
+            | BinanceErr::Unauthorized { endpoint, .. }
+            | BinanceErr::UnexpectedStatus { endpoint, .. } => Some(endpoint.as_str()),
+            BinanceErr::MalformedPayload { endpoint, .. } => endpoint.as_deref(),
+            _ => None
+        }
+    }
+
+    /// Transient failures: the same request may succeed if sent again later
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            BinanceErr::HTTP(e) => e.is_connect() || e.is_timeout(),
+            BinanceErr::BinanceContentError(e) => e.error_code().is_transient(),
+            BinanceErr::RateLimited { .. } | BinanceErr::ServerError { .. } => true,
+            _ => false
+        }
+    }
+
+    pub fn is_rate_limited(&self) -> bool {
+        match self {
+            BinanceErr::RateLimited { .. } => true,
+            BinanceErr::BinanceContentError(e) => matches!(
+                e.error_code(),
+                BinanceErrorCode::TooManyRequests | BinanceErrorCode::TooManyOrders
+            ),
+            _ => false
+        }
+    }
+
+    /// An order rejected by one of the symbol or exchange filters, by Binance or locally
+    pub fn is_filter_failure(&self) -> bool {
+        self.filter_failure().is_some()
+    }
+
+    pub fn filter_failure(&self) -> Option<FilterFailure> {
+        match self {
+            BinanceErr::BinanceContentError(e) => e.filter_failure(),
+            BinanceErr::FilterViolation { filter, .. } => Some(filter.clone()),
+            _ => None
+        }
+    }
+
+    pub fn reject_reason(&self) -> Option<RejectReason> {
+        match self {
+            BinanceErr::BinanceContentError(e) => e.reject_reason(),
+            _ => None
+        }
+    }
+
+    pub fn is_insufficient_balance(&self) -> bool {
+        self.reject_reason() == Some(RejectReason::InsufficientBalance)
+    }
+
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(self, BinanceErr::Unauthorized { .. })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::client::parse_response;
+    use crate::error::other_err::BinanceContentError;
+    use crate::error::*;
+    use crate::model::ServerTime;
+
+    fn content_error(code: i16, msg: &str) -> BinanceErr {
+        let mut e: BinanceContentError = serde_json::from_value(serde_json::json!({ "code": code, "msg": msg })).unwrap();
+        e.status = 400;
+        e.endpoint = "/api/v3/order".into();
+        BinanceErr::BinanceContentError(e)
+    }
+
+    #[test]
+    fn maps_error_codes() {
+        let codes = [
+            (-1000, BinanceErrorCode::Unknown, true),
+            (-1003, BinanceErrorCode::TooManyRequests, true),
+            (-1007, BinanceErrorCode::Timeout, true),
+            (-1013, BinanceErrorCode::InvalidMessage, false),
+            (-1021, BinanceErrorCode::InvalidTimestamp, true),
+            (-1022, BinanceErrorCode::InvalidSignature, false),
+            (-1121, BinanceErrorCode::BadSymbol, false),
+            (-2010, BinanceErrorCode::NewOrderRejected, false),
+            (-2013, BinanceErrorCode::NoSuchOrder, false),
+            (-2015, BinanceErrorCode::RejectedMbxKey, false),
+            (-9999, BinanceErrorCode::Other(-9999), false),
+        ];
+        for (code, expected, transient) in codes {
+            assert_eq!(BinanceErrorCode::from(code), expected);
+            assert_eq!(content_error(code, "").is_retryable(), transient, "{}", code);
+        }
+    }
+
+    #[test]
+    fn parses_filter_failures_and_reject_reasons() {
+        let filters = [
+            ("Filter failure: LOT_SIZE", Some(FilterFailure::LotSize)),
+            ("Filter failure: NOTIONAL", Some(FilterFailure::Notional)),
+            ("Filter failure: PERCENT_PRICE_BY_SIDE", Some(FilterFailure::PercentPriceBySide)),
+            ("Filter failure: NEW_FILTER", Some(FilterFailure::Other("NEW_FILTER".into()))),
+            ("Illegal characters found in parameter 'symbol'", None),
+        ];
+        for (msg, expected) in filters {
+            assert_eq!(content_error(-1013, msg).filter_failure(), expected, "{}", msg);
+        }
+
+        let reasons = [
+            (-2010, "Account has insufficient balance for requested action.", Some(RejectReason::InsufficientBalance)),
+            (-2010, "Order would immediately match and take.", Some(RejectReason::WouldImmediatelyMatch)),
+            (-2011, "Unknown order sent.", Some(RejectReason::UnknownOrder)),
+            (-2010, "Something new.", Some(RejectReason::Other("Something new.".into()))),
+            // The reason only counts with the code it belongs to
+            (-1013, "Account has insufficient balance for requested action.", None),
+        ];
+        for (code, msg, expected) in reasons {
+            let error = content_error(code, msg);
+            assert_eq!(error.is_insufficient_balance(), expected == Some(RejectReason::InsufficientBalance));
+            assert_eq!(error.reject_reason(), expected, "{}", msg);
+        }
+    }
+
+    #[test]
+    fn status_and_endpoint_of_each_error() {
+        let errors = [
+            (content_error(-2010, ""), Some(400), Some("/api/v3/order")),
+            (BinanceErr::RateLimited { retry_after: Duration::from_secs(1), status: None, endpoint: "/api/v3/depth".into() }, None, Some("/api/v3/depth")),
+            (BinanceErr::ServerError { status: 503, endpoint: "/api/v3/order".into(), msg: String::new() }, Some(503), Some("/api/v3/order")),
+            (BinanceErr::Unauthorized { code: Some(-2015), endpoint: "/api/v3/account".into(), msg: String::new() }, Some(401), Some("/api/v3/account")),
+            (BinanceErr::UnexpectedStatus { status: 404, endpoint: "/api/v3/nope".into(), msg: String::new() }, Some(404), Some("/api/v3/nope")),
+            (parse_response::<ServerTime>("/api/v3/time", "{}").err().unwrap(), None, Some("/api/v3/time")),
+            (BinanceErr::from_str("misc".into()), None, None),
+        ];
+        for (error, status, endpoint) in errors {
+            assert_eq!(error.status(), status, "{}", error);
+            assert_eq!(error.endpoint(), endpoint, "{}", error);
+        }
+    }
 }
@@ -1,8 +1,9 @@
-use serde_json::from_str;
+use std::time::Duration;
 
 use crate::client::*;
 use crate::error::*;
 use crate::model::*;
+use crate::time_sync::TimeSyncTask;
 
 #[derive(Clone)]
 pub struct General {
@@ -21,22 +22,35 @@ impl General {
     pub async fn get_server_time(&self) -> Result<ServerTime, BinanceErr> {
         let data: String = self.client.get("/api/v3/time", "").await?;
 
-        let server_time: ServerTime = from_str(data.as_str())?;
+        let server_time: ServerTime = parse_response("/api/v3/time", &data)?;
 
         Ok(server_time)
     }
 
+    // Measure the offset between the local and the server clock, used to timestamp signed requests
+    // on every handle sharing this client
+    pub async fn sync_time(&self) -> Result<(), BinanceErr> {
+        self.client.time_sync().sync(&self.client, "/api/v3/time").await
+    }
+
+    // Keep the clock offset up to date in the background, every `interval` until the task is dropped
+    pub fn start_time_sync(&self, interval: Duration) -> TimeSyncTask {
+        self.client.time_sync().start(self.client.clone(), "/api/v3/time", interval)
+    }
+
     // Obtain exchange information
     // - Current exchange trading rules and symbol information
     pub async fn exchange_info(&self) -> Result<ExchangeInformation, BinanceErr> {
         let data: String = self.client.get("/api/v3/exchangeInfo", "").await?;
 
-        let info: ExchangeInformation = from_str(data.as_str())?;
+        let info: ExchangeInformation = parse_response("/api/v3/exchangeInfo", &data)?;
+        self.client.rate_limiter().seed(&info.rate_limits);
 
         Ok(info)
     }
 
     // Get Symbol information
+    // - Downloads the whole exchange information, use a `SymbolRegistry` to look symbols up repeatedly
     pub async fn get_symbol_info<S>(&self, symbol: S) -> Result<Symbol, BinanceErr>
         where
             S: Into<String>,
@@ -49,7 +63,7 @@ impl General {
                         return Ok(item);
                     }
                 }
-                Err(BinanceErr::from_str(format!("Symbol not found")))
+                Err(BinanceErr::UnknownSymbol(upper_symbol))
             }
             Err(e) => Err(e),
         }
@@ -6,10 +6,23 @@ pub mod model;
 pub mod websocket;
 pub mod client;
 pub mod account;
+pub mod order;
+pub mod order_book;
+pub mod numeric;
 pub mod util;
 pub mod general;
 pub mod market;
 pub mod userstream;
 pub mod binance_futures;
 pub mod api;
+pub mod config;
+mod connection;
+pub mod rate_limit;
+pub mod retry;
+pub mod signer;
+pub mod symbols;
+pub mod time_sync;
+pub mod transport;
+#[cfg(any(test, feature = "test-support"))]
+pub mod mock_server;
 
@@ -1,6 +1,6 @@
 use std::collections::BTreeMap;
 
-use serde_json::{from_str, Value};
+use serde_json::Value;
 
 use crate::client::*;
 use crate::error::*;
@@ -27,7 +27,7 @@ impl Market {
 
         let data = self.client.get("/api/v3/depth", &request).await?;
 
-        let order_book: OrderBook = from_str(data.as_str())?;
+        let order_book: OrderBook = parse_response("/api/v3/depth", &data)?;
 
         Ok(order_book)
     }
@@ -46,7 +46,7 @@ impl Market {
 
         let data = self.client.get("/api/v3/depth", &request).await?;
 
-        let order_book: OrderBook = from_str(data.as_str())?;
+        let order_book: OrderBook = parse_response("/api/v3/depth", &data)?;
 
         Ok(order_book)
     }
@@ -55,7 +55,7 @@ impl Market {
     pub async fn get_all_prices(&self) -> Result<Prices, BinanceErr> {
         let data = self.client.get("/api/v3/ticker/price", "").await?;
 
-        let prices: Prices = from_str(data.as_str())?;
+        let prices: Prices = parse_response("/api/v3/ticker/price", &data)?;
 
         Ok(prices)
     }
@@ -71,7 +71,7 @@ impl Market {
         let request = build_request(&parameters);
 
         let data = self.client.get("/api/v3/ticker/price", &request).await?;
-        let symbol_price: SymbolPrice = from_str(data.as_str())?;
+        let symbol_price: SymbolPrice = parse_response("/api/v3/ticker/price", &data)?;
 
         Ok(symbol_price)
     }
@@ -87,7 +87,7 @@ impl Market {
         let request = build_request(&parameters);
 
         let data = self.client.get("/api/v3/avgPrice", &request).await?;
-        let average_price: AveragePrice = from_str(data.as_str())?;
+        let average_price: AveragePrice = parse_response("/api/v3/avgPrice", &data)?;
 
         Ok(average_price)
     }
@@ -97,7 +97,7 @@ impl Market {
     pub async fn get_all_book_tickers(&self) -> Result<BookTickers, BinanceErr> {
         let data = self.client.get("/api/v3/ticker/bookTicker", "").await?;
 
-        let book_tickers: BookTickers = from_str(data.as_str())?;
+        let book_tickers: BookTickers = parse_response("/api/v3/ticker/bookTicker", &data)?;
 
         Ok(book_tickers)
     }
@@ -116,7 +116,7 @@ impl Market {
             .client
             .get("/api/v3/ticker/bookTicker", &request)
             .await?;
-        let ticker: Tickers = from_str(data.as_str())?;
+        let ticker: Tickers = parse_response("/api/v3/ticker/bookTicker", &data)?;
 
         Ok(ticker)
     }
@@ -133,7 +133,7 @@ impl Market {
 
         let data = self.client.get("/api/v3/ticker/24hr", &request).await?;
 
-        let stats: PriceStats = from_str(data.as_str())?;
+        let stats: PriceStats = parse_response("/api/v3/ticker/24hr", &data)?;
 
         Ok(stats)
     }
@@ -174,25 +174,13 @@ impl Market {
         let request = build_request(&parameters);
 
         let data = self.client.get("/api/v3/klines", &request).await?;
-        let parsed_data: Vec<Vec<Value>> = from_str(data.as_str())?;
+        let parsed_data: Vec<Vec<Value>> = parse_response("/api/v3/klines", &data)?;
 
         let klines = KlineSummaries::AllKlineSummaries(
             parsed_data
                 .iter()
-                .map(|row| KlineSummary {
-                    open_time: to_i64(&row[0]),
-                    open: to_f64(&row[1]),
-                    high: to_f64(&row[2]),
-                    low: to_f64(&row[3]),
-                    close: to_f64(&row[4]),
-                    volume: to_f64(&row[5]),
-                    close_time: to_i64(&row[6]),
-                    quote_asset_volume: to_f64(&row[7]),
-                    number_of_trades: to_i64(&row[8]),
-                    taker_buy_base_asset_volume: to_f64(&row[9]),
-                    taker_buy_quote_asset_volume: to_f64(&row[10]),
-                })
-                .collect(),
+                .map(|row| to_kline_summary(row))
+                .collect::<Result<Vec<KlineSummary>, BinanceErr>>()?,
         );
         Ok(klines)
     }
@@ -0,0 +1,581 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use futures::{SinkExt, StreamExt};
+use hyper::{Body, Request, Response, Uri};
+use hyper::service::{make_service_fn, service_fn};
+use reqwest::Method;
+use reqwest::header::{CONTENT_TYPE, HeaderValue};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, Notify};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::handshake::server::{Callback, ErrorResponse, Request as WsRequest, Response as WsResponse};
+use url::form_urlencoded;
+
+use crate::config::{BinanceEnvironment, Config};
+use crate::error::BinanceErr;
+use crate::transport::{mock_response, MockTransport, TransportRequest, TransportResponse};
+use crate::util::get_timestamp;
+
+/// Local stand-in for the Binance REST and websocket APIs, for deterministic offline tests.
+///
+/// Serves canned spot (`/api/v3`) and futures (`/fapi/v1`) responses for the endpoints this
+/// crate calls, keeps the orders it is sent, and streams whatever `push_event` is given to the
+/// websocket connections subscribed to that stream. Any route can be scripted with `on` / `once`
+/// exactly like `MockTransport`, which takes precedence over the canned data.
+///
+/// Enabled with the `test-support` feature. Both servers stop when it is dropped.
+pub struct MockBinanceServer {
+    state: Arc<MockServerState>,
+    rest_endpoint: String,
+    ws_endpoint: String,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+struct MockServerState {
+    scripted: MockTransport,
+    rest_endpoint: String,
+    orders: Mutex<Vec<Value>>,
+    next_id: AtomicU64,
+    events: broadcast::Sender<(String, String)>,
+    subscriptions: Mutex<HashMap<u64, HashSet<String>>>,
+    subscriptions_changed: Notify,
+    reject_subscriptions: AtomicBool,
+    disconnect: broadcast::Sender<()>,
+}
+
+impl MockBinanceServer {
+    /// Start both servers on free local ports
+    pub async fn start() -> Result<Self, BinanceErr> {
+        let ws_listener = TcpListener::bind("127.0.0.1:0").await?;
+        let ws_endpoint = format!("ws://{}/stream", ws_listener.local_addr()?);
+
+        let rest_server = hyper::Server::try_bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .map_err(|e| BinanceErr::from_str(format!("Failed to start the mock REST server: {}", e)))?;
+        let rest_endpoint = format!("http://{}", rest_server.local_addr());
+
+        let state = Arc::new(MockServerState {
+            scripted: MockTransport::new(),
+            rest_endpoint: rest_endpoint.clone(),
+            orders: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(1),
+            events: broadcast::channel(1024).0,
+            subscriptions: Mutex::new(HashMap::new()),
+            subscriptions_changed: Notify::new(),
+            reject_subscriptions: AtomicBool::new(false),
+            disconnect: broadcast::channel(1).0,
+        });
+
+        let rest_state = state.clone();
+        let rest_server = rest_server.serve(make_service_fn(move |_| {
+            let state = rest_state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| handle_rest(state.clone(), request)))
+            }
+        }));
+        let rest_task = tokio::spawn(async move {
+            rest_server.await.ok();
+        });
+
+        let ws_state = state.clone();
+        let ws_task = tokio::spawn(async move {
+            while let Ok((stream, _)) = ws_listener.accept().await {
+                tokio::spawn(handle_ws(ws_state.clone(), stream));
+            }
+        });
+
+        Ok(MockBinanceServer { state, rest_endpoint, ws_endpoint, tasks: vec![rest_task, ws_task] })
+    }
+
+    /// e.g. `http://127.0.0.1:40123`, serving both the spot and the futures paths
+    pub fn rest_endpoint(&self) -> &str {
+        &self.rest_endpoint
+    }
+
+    /// e.g. `ws://127.0.0.1:40124/stream`
+    pub fn ws_endpoint(&self) -> &str {
+        &self.ws_endpoint
+    }
+
+    pub fn environment(&self) -> BinanceEnvironment {
+        BinanceEnvironment::Custom { rest: self.rest_endpoint.clone(), ws: self.ws_endpoint.clone() }
+    }
+
+    /// Default configuration pointed at this server
+    pub fn config(&self) -> Config {
+        Config::from_environment(self.environment())
+    }
+
+    /// Answer every `method` request on `path` with this status and body
+    pub fn on<S: Into<String>>(&self, method: Method, path: &str, status: u16, body: S) -> &Self {
+        self.state.scripted.on(method, path, status, body);
+        self
+    }
+
+    /// Answer the next `method` request on `path` with this status and body
+    pub fn once<S: Into<String>>(&self, method: Method, path: &str, status: u16, body: S) -> &Self {
+        self.state.scripted.once(method, path, status, body);
+        self
+    }
+
+    /// REST requests received so far, oldest first
+    pub fn requests(&self) -> Vec<TransportRequest> {
+        self.state.scripted.requests()
+    }
+
+    /// Send `data` to every connection subscribed to `stream`, e.g. `btcusdt@trade` or a listen key
+    pub fn push_event<S: Into<String>>(&self, stream: S, data: Value) {
+        // Nobody connected yet is not an error, the event is simply missed like on the exchange
+        self.state.events.send((stream.into(), data.to_string())).ok();
+    }
+
+    /// Streams currently subscribed to by at least one connection
+    pub fn subscriptions(&self) -> HashSet<String> {
+        let subscriptions = self.state.subscriptions.lock().unwrap_or_else(|e| e.into_inner());
+        subscriptions.values().flatten().cloned().collect()
+    }
+
+    /// Websocket connections currently open
+    pub fn connection_count(&self) -> usize {
+        self.state.subscriptions.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Close every websocket connection, like Binance does after 24 hours
+    pub fn disconnect_websockets(&self) {
+        self.state.disconnect.send(()).ok();
+    }
+
+    /// Answer every SUBSCRIBE with an error from now on, or again normally
+    pub fn reject_subscriptions(&self, reject: bool) {
+        self.state.reject_subscriptions.store(reject, Ordering::Relaxed);
+    }
+
+    /// Wait until a connection subscribes to `stream`, so events pushed afterwards are not missed
+    pub async fn wait_for_subscription(&self, stream: &str) {
+        loop {
+            let changed = self.state.subscriptions_changed.notified();
+            if self.subscriptions().contains(stream) {
+                return;
+            }
+            changed.await;
+        }
+    }
+}
+
+impl Drop for MockBinanceServer {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+async fn handle_rest(state: Arc<MockServerState>, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let (parts, body) = request.into_parts();
+    let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+    let path_and_query = parts.uri.path_and_query().map_or("/", |p| p.as_str());
+    let request = TransportRequest {
+        method: parts.method,
+        url: format!("{}{}", state.rest_endpoint, path_and_query),
+        headers: parts.headers,
+        body: if body.is_empty() { None } else { Some(String::from_utf8_lossy(&body).into_owned()) },
+    };
+
+    let response = match state.scripted.respond(request.clone()) {
+        Some(response) => response,
+        None => canned_response(&state, &request)
+    };
+
+    let mut reply = Response::new(Body::from(response.body));
+    *reply.status_mut() = response.status;
+    *reply.headers_mut() = response.headers;
+    reply.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    Ok(reply)
+}
+
+fn canned_response(state: &MockServerState, request: &TransportRequest) -> TransportResponse {
+    let path = request.path();
+    let route = path.strip_prefix("/api/v3").or_else(|| path.strip_prefix("/fapi/v1")).unwrap_or(path);
+    let params = request_params(request);
+    let now = get_timestamp().unwrap_or_default();
+
+    let signed = matches!(route, "/order" | "/order/test" | "/openOrders" | "/account" | "/myTrades");
+    if signed {
+        if !request.headers.contains_key("x-mbx-apikey") {
+            return error_response(401, -2014, "API-key format invalid.");
+        }
+        if !params.contains_key("signature") || !params.contains_key("timestamp") {
+            return error_response(400, -1102, "Mandatory parameter 'signature' was not sent, was empty/null, or malformed.");
+        }
+    }
+
+    let body = match (&request.method, route) {
+        (&Method::GET, "/ping") => json!({}),
+        (&Method::GET, "/time") => json!({ "serverTime": now }),
+        (&Method::GET, "/exchangeInfo") => exchange_info(now),
+        (&Method::GET, "/depth") => json!({
+            "lastUpdateId": 1027024,
+            "E": now,
+            "T": now,
+            "bids": [["4.00000000", "431.00000000"], ["3.99000000", "9.00000000"]],
+            "asks": [["4.00000200", "12.00000000"], ["4.01000000", "18.00000000"]]
+        }),
+        (&Method::GET, "/klines") => json!([
+            [1499040000000u64, "0.01634790", "0.80000000", "0.01575800", "0.01577100", "148976.11427815", 1499644799999u64, "2434.19055334", 308, "1756.87402397", "28.46694368", "0"],
+            [1499644800000u64, "0.01577100", "0.01800000", "0.01570000", "0.01790000", "95043.40000000", 1500249599999u64, "1602.45501100", 211, "1012.00000000", "17.21000000", "0"]
+        ]),
+        (&Method::GET, "/account") => json!({
+            "makerCommission": 15,
+            "takerCommission": 15,
+            "buyerCommission": 0,
+            "sellerCommission": 0,
+            "canTrade": true,
+            "canWithdraw": true,
+            "canDeposit": true,
+            "balances": [
+                { "asset": "BTC", "free": "4723846.89208129", "locked": "0.00000000" },
+                { "asset": "USDT", "free": "100000.00000000", "locked": "0.00000000" }
+            ]
+        }),
+        (&Method::POST, "/order/test") => json!({}),
+        (&Method::POST, "/order") => place_order(state, &params, now),
+        (&Method::GET, "/order") => match find_order(state, &params) {
+            Some(order) => order,
+            None => return error_response(400, -2013, "Order does not exist.")
+        },
+        (&Method::DELETE, "/order") => match cancel_order(state, &params) {
+            Some(canceled) => canceled,
+            None => return error_response(400, -2011, "Unknown order sent.")
+        },
+        (&Method::GET, "/openOrders") => Value::Array(open_orders(state, &params, false)),
+        (&Method::DELETE, "/openOrders") => Value::Array(open_orders(state, &params, true)),
+        (&Method::GET, "/myTrades") => json!([]),
+        (&Method::POST, "/userDataStream") => {
+            let id = state.next_id.fetch_add(1, Ordering::Relaxed);
+            json!({ "listenKey": format!("mockListenKey{:050}", id) })
+        }
+        (&Method::PUT, "/userDataStream") | (&Method::DELETE, "/userDataStream") => json!({}),
+        _ => {
+            return error_response(404, -1000, &format!("No mock response for {} {}", request.method, path));
+        }
+    };
+    mock_response(200, body.to_string())
+}
+
+fn error_response(status: u16, code: i16, msg: &str) -> TransportResponse {
+    mock_response(status, json!({ "code": code, "msg": msg }).to_string())
+}
+
+/// Parameters from both the query string and a form encoded body
+fn request_params(request: &TransportRequest) -> HashMap<String, String> {
+    let body = request.body.as_deref().unwrap_or("");
+    form_urlencoded::parse(request.query().as_bytes())
+        .chain(form_urlencoded::parse(body.as_bytes()))
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect()
+}
+
+fn exchange_info(now: u64) -> Value {
+    let symbol = |symbol: &str, base: &str, quote: &str| json!({
+        "symbol": symbol,
+        "status": "TRADING",
+        "baseAsset": base,
+        "baseAssetPrecision": 8,
+        "quoteAsset": quote,
+        "quotePrecision": 8,
+        "pricePrecision": 2,
+        "quantityPrecision": 5,
+        "maintMarginPercent": "2.5000",
+        "requiredMarginPercent": "5.0000",
+        "orderTypes": ["LIMIT", "LIMIT_MAKER", "MARKET", "STOP_LOSS_LIMIT", "TAKE_PROFIT_LIMIT"],
+        "timeInForce": ["GTC", "IOC", "FOK"],
+        "icebergAllowed": true,
+        "isSpotTradingAllowed": true,
+        "isMarginTradingAllowed": true,
+        "filters": [
+            { "filterType": "PRICE_FILTER", "minPrice": "0.01000000", "maxPrice": "1000000.00000000", "tickSize": "0.01000000" },
+            { "filterType": "LOT_SIZE", "minQty": "0.00001000", "maxQty": "9000.00000000", "stepSize": "0.00001000" },
+            { "filterType": "PERCENT_PRICE_BY_SIDE", "bidMultiplierUp": "5", "bidMultiplierDown": "0.2", "askMultiplierUp": "5", "askMultiplierDown": "0.2", "avgPriceMins": 5 },
+            { "filterType": "NOTIONAL", "minNotional": "5.00000000", "applyMinToMarket": true, "maxNotional": "9000000.00000000", "applyMaxToMarket": false, "avgPriceMins": 5 },
+            { "filterType": "TRAILING_DELTA", "minTrailingAboveDelta": 10, "maxTrailingAboveDelta": 2000, "minTrailingBelowDelta": 10, "maxTrailingBelowDelta": 2000 },
+            { "filterType": "MAX_NUM_ORDERS", "limit": 200 },
+            { "filterType": "MAX_NUM_ORDER_AMENDS", "maxNumOrderAmends": 10 }
+        ]
+    });
+    json!({
+        "timezone": "UTC",
+        "serverTime": now,
+        "rateLimits": [
+            { "rateLimitType": "REQUEST_WEIGHT", "interval": "MINUTE", "intervalNum": 1, "limit": 6000 },
+            { "rateLimitType": "ORDERS", "interval": "SECOND", "intervalNum": 10, "limit": 100 },
+            { "rateLimitType": "ORDERS", "interval": "DAY", "intervalNum": 1, "limit": 200000 }
+        ],
+        "exchangeFilters": [],
+        "symbols": [symbol("BTCUSDT", "BTC", "USDT"), symbol("ETHBTC", "ETH", "BTC")]
+    })
+}
+
+/// Market orders fill right away, anything else rests on the book as `NEW`
+fn place_order(state: &MockServerState, params: &HashMap<String, String>, now: u64) -> Value {
+    let param = |name: &str, default: &str| params.get(name).map_or(default, String::as_str).to_string();
+    let order_id = state.next_id.fetch_add(1, Ordering::Relaxed);
+    let order_type = param("type", "LIMIT");
+    let quantity = param("quantity", "0");
+    let filled = order_type == "MARKET";
+    let client_order_id = params
+        .get("newClientOrderId")
+        .cloned()
+        .unwrap_or_else(|| format!("mock{}", order_id));
+
+    let order = json!({
+        "symbol": param("symbol", ""),
+        "orderId": order_id,
+        "clientOrderId": client_order_id,
+        "price": param("price", "0"),
+        "origQty": quantity,
+        "executedQty": if filled { quantity.clone() } else { "0".into() },
+        "cummulativeQuoteQty": "0",
+        "status": if filled { "FILLED" } else { "NEW" },
+        "timeInForce": param("timeInForce", "GTC"),
+        "type": order_type,
+        "side": param("side", "BUY"),
+        "stopPrice": param("stopPrice", "0"),
+        "icebergQty": param("icebergQty", "0"),
+        "time": now,
+        "transactTime": now,
+        "fills": []
+    });
+    state.orders.lock().unwrap_or_else(|e| e.into_inner()).push(order.clone());
+    order
+}
+
+fn order_matches(order: &Value, params: &HashMap<String, String>) -> bool {
+    let by_id = params.get("orderId").map(|id| id.parse().ok() == order["orderId"].as_u64());
+    let by_client_id = params.get("origClientOrderId").map(|id| order["clientOrderId"] == id.as_str());
+    order["symbol"] == params.get("symbol").map_or("", String::as_str)
+        && by_id.or(by_client_id).unwrap_or(false)
+}
+
+fn find_order(state: &MockServerState, params: &HashMap<String, String>) -> Option<Value> {
+    let orders = state.orders.lock().unwrap_or_else(|e| e.into_inner());
+    orders.iter().find(|order| order_matches(order, params)).cloned()
+}
+
+fn cancel_order(state: &MockServerState, params: &HashMap<String, String>) -> Option<Value> {
+    let mut orders = state.orders.lock().unwrap_or_else(|e| e.into_inner());
+    let order = orders.iter_mut().find(|order| order_matches(order, params) && order["status"] == "NEW")?;
+    order["status"] = json!("CANCELED");
+    Some(json!({
+        "symbol": order["symbol"],
+        "origClientOrderId": order["clientOrderId"],
+        "orderId": order["orderId"],
+        "clientOrderId": params.get("newClientOrderId").cloned().unwrap_or_else(|| "mockCancel".into())
+    }))
+}
+
+// `Option::is_none_or` would need Rust 1.82
+#[allow(clippy::unnecessary_map_or)]
+fn open_orders(state: &MockServerState, params: &HashMap<String, String>, cancel: bool) -> Vec<Value> {
+    let mut orders = state.orders.lock().unwrap_or_else(|e| e.into_inner());
+    let symbol = params.get("symbol");
+    orders
+        .iter_mut()
+        .filter(|order| order["status"] == "NEW" && symbol.map_or(true, |s| order["symbol"] == s.as_str()))
+        .map(|order| {
+            if cancel {
+                order["status"] = json!("CANCELED");
+            }
+            order.clone()
+        })
+        .collect()
+}
+
+/// Handshake callback keeping the requested uri, which decides the initial streams
+struct RecordUri<'a>(&'a mut Option<Uri>);
+
+impl Callback for RecordUri<'_> {
+    fn on_request(self, request: &WsRequest, response: WsResponse) -> Result<WsResponse, ErrorResponse> {
+        *self.0 = Some(request.uri().clone());
+        Ok(response)
+    }
+}
+
+/// `/stream?streams=a/b` (combined, wrapped in `{"stream", "data"}`) or `/ws/<stream>` (raw),
+/// plus SUBSCRIBE / UNSUBSCRIBE / LIST_SUBSCRIPTIONS
+async fn handle_ws(state: Arc<MockServerState>, stream: TcpStream) {
+    let mut uri = None;
+    let mut ws = match tokio_tungstenite::accept_hdr_async(stream, RecordUri(&mut uri)).await {
+        Ok(ws) => ws,
+        Err(_) => return
+    };
+    let uri = match uri {
+        Some(uri) => uri,
+        None => return
+    };
+
+    let combined = uri.path().starts_with("/stream");
+    let mut initial: HashSet<String> = uri
+        .query()
+        .and_then(|q| form_urlencoded::parse(q.as_bytes()).find(|(k, _)| k == "streams"))
+        .map(|(_, streams)| streams.split('/').map(String::from).collect())
+        .unwrap_or_default();
+    if let Some(raw) = uri.path().strip_prefix("/ws/") {
+        initial.insert(raw.to_string());
+    }
+
+    let connection = state.next_id.fetch_add(1, Ordering::Relaxed);
+    state.update_subscriptions(connection, |subscriptions| subscriptions.extend(initial));
+    let mut events = state.events.subscribe();
+    let mut disconnect = state.disconnect.subscribe();
+
+    loop {
+        tokio::select! {
+            _ = disconnect.recv() => {
+                ws.close(None).await.ok();
+                break;
+            }
+            message = ws.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue
+                };
+                if let Some(reply) = state.control_reply(connection, &text) {
+                    if ws.send(Message::Text(reply)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            event = events.recv() => {
+                let (stream, data) = match event {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break
+                };
+                if !state.is_subscribed(connection, &stream) {
+                    continue;
+                }
+                let text = if combined {
+                    format!(r#"{{"stream":{},"data":{}}}"#, Value::from(stream), data)
+                } else {
+                    data
+                };
+                if ws.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    state.subscriptions.lock().unwrap_or_else(|e| e.into_inner()).remove(&connection);
+    state.subscriptions_changed.notify_waiters();
+}
+
+impl MockServerState {
+    fn update_subscriptions<F: FnOnce(&mut HashSet<String>)>(&self, connection: u64, update: F) {
+        update(self.subscriptions.lock().unwrap_or_else(|e| e.into_inner()).entry(connection).or_default());
+        self.subscriptions_changed.notify_waiters();
+    }
+
+    fn is_subscribed(&self, connection: u64, stream: &str) -> bool {
+        let subscriptions = self.subscriptions.lock().unwrap_or_else(|e| e.into_inner());
+        subscriptions.get(&connection).is_some_and(|s| s.contains(stream))
+    }
+
+    fn control_reply(&self, connection: u64, text: &str) -> Option<String> {
+        let request: Value = serde_json::from_str(text).ok()?;
+        let params: Vec<String> = request["params"]
+            .as_array()
+            .map(|params| params.iter().filter_map(|p| p.as_str().map(String::from)).collect())
+            .unwrap_or_default();
+        let result = match request["method"].as_str()? {
+            "SUBSCRIBE" if self.reject_subscriptions.load(Ordering::Relaxed) => {
+                return Some(json!({ "code": 2, "msg": "Invalid request: subscription rejected", "id": request["id"] }).to_string())
+            }
+            "SUBSCRIBE" => {
+                self.update_subscriptions(connection, |subscriptions| subscriptions.extend(params));
+                Value::Null
+            }
+            "UNSUBSCRIBE" => {
+                self.update_subscriptions(connection, |subscriptions| {
+                    params.iter().for_each(|p| {
+                        subscriptions.remove(p);
+                    })
+                });
+                Value::Null
+            }
+            "LIST_SUBSCRIPTIONS" => {
+                let subscriptions = self.subscriptions.lock().unwrap_or_else(|e| e.into_inner());
+                json!(subscriptions.get(&connection).map(|s| s.iter().collect::<Vec<_>>()))
+            }
+            _ => return Some(json!({ "code": 2, "msg": "Invalid request: unknown method", "id": request["id"] }).to_string())
+        };
+        Some(json!({ "result": result, "id": request["id"] }).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+
+    use crate::account::Account;
+    use crate::api::Binance;
+    use crate::error::BinanceErrorCode;
+    use crate::general::General;
+    use crate::market::Market;
+    use crate::mock_server::*;
+    use crate::model::KlineSummaries;
+    use crate::numeric::Number;
+    use crate::userstream::{UserStream, UserStreamAsync};
+    use crate::websocket::WebsocketEvent;
+
+    #[tokio::test]
+    async fn serves_the_rest_endpoints() {
+        let server = MockBinanceServer::start().await.unwrap();
+        let config = server.config();
+
+        let general = General::new_with_config(Some("key".into()), Some("secret".into()), &config);
+        general.ping().await.unwrap();
+        general.sync_time().await.unwrap();
+        let info = general.exchange_info().await.unwrap();
+        assert_eq!(info.symbols.len(), 2);
+
+        let market = Market::new_with_config(None, None, &config);
+        assert_eq!(market.get_depth("BTCUSDT").await.unwrap().bids.len(), 2);
+        let KlineSummaries::AllKlineSummaries(klines) = market.get_klines("BTCUSDT", "1d", None, None, None).await.unwrap();
+        assert_eq!(klines.len(), 2);
+
+        let account = Account::new_with_config(Some("key".into()), Some("secret".into()), &config);
+        assert_eq!(account.get_balance("USDT").await.unwrap().free, Number::from(100000));
+        let transaction = account.limit_buy("BTCUSDT", 1, Number::from(100)).await.unwrap();
+        assert_eq!(account.order_status("BTCUSDT", transaction.order_id).await.unwrap().status, "NEW");
+        account.cancel_order("BTCUSDT", transaction.order_id).await.unwrap();
+        let error = account.cancel_order("BTCUSDT", transaction.order_id).await.unwrap_err();
+        assert_eq!(error.error_code(), Some(BinanceErrorCode::CancelRejected));
+
+        server.once(Method::GET, "/api/v3/ping", 503, "Service Unavailable");
+        assert!(general.ping().await.is_ok(), "the retry gets the canned answer");
+        assert_eq!(server.requests().iter().filter(|r| r.path() == "/api/v3/ping").count(), 3);
+    }
+
+    #[tokio::test]
+    async fn streams_user_data_to_the_listen_key() {
+        let server = MockBinanceServer::start().await.unwrap();
+        server.on(Method::POST, "/api/v3/userDataStream", 200, r#"{"listenKey":"testListenKey"}"#);
+        let mut user_stream = UserStream::new_with_config(Some("key".into()), None, &server.config());
+        let mut events = user_stream.subscribe().await.unwrap();
+        server.wait_for_subscription("testListenKey").await;
+
+        server.push_event("testListenKey", json!({
+            "e": "balanceUpdate", "E": 1573200697110u64, "a": "BTC", "d": "100.00000000", "T": 1573200697068u64
+        }));
+        match events.try_next().await.unwrap().map(|e| e.event) {
+            Some(WebsocketEvent::BalanceUpdate(update)) => assert_eq!(update.asset, "BTC"),
+            other => panic!("unexpected event {:?}", other)
+        }
+    }
+}
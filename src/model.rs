@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::numeric::Number;
+use crate::order::{ExecutionType, OrderSide, OrderStatus, OrderType, SelfTradePreventionMode, TimeInForce};
 use crate::websocket::WebsocketEvent;
 
 #[derive(Serialize, Deserialize)]
@@ -54,31 +56,73 @@ pub enum Filters {
     #[serde(rename = "PRICE_FILTER")]
     #[serde(rename_all = "camelCase")]
     PriceFilter {
-        min_price: String,
-        max_price: String,
-        tick_size: String,
+        #[serde(with = "string_or_float")]
+        min_price: Number,
+        #[serde(with = "string_or_float")]
+        max_price: Number,
+        #[serde(with = "string_or_float")]
+        tick_size: Number,
     },
     #[serde(rename = "PERCENT_PRICE")]
     #[serde(rename_all = "camelCase")]
     PercentPrice {
-        multiplier_up: String,
-        multiplier_down: String,
+        #[serde(with = "string_or_float")]
+        multiplier_up: Number,
+        #[serde(with = "string_or_float")]
+        multiplier_down: Number,
         avg_price_mins: Option<f64>,
     },
     #[serde(rename = "LOT_SIZE")]
     #[serde(rename_all = "camelCase")]
     LotSize {
-        min_qty: String,
-        max_qty: String,
-        step_size: String,
+        #[serde(with = "string_or_float")]
+        min_qty: Number,
+        #[serde(with = "string_or_float")]
+        max_qty: Number,
+        #[serde(with = "string_or_float")]
+        step_size: Number,
     },
     #[serde(rename = "MIN_NOTIONAL")]
     #[serde(rename_all = "camelCase")]
     MinNotional {
-        min_notional: Option<String>,
+        #[serde(default, with = "string_or_float_opt")]
+        min_notional: Option<Number>,
         apply_to_market: Option<bool>,
         avg_price_mins: Option<f64>,
     },
+    /// Replaces MIN_NOTIONAL on spot, with an upper bound as well
+    #[serde(rename = "NOTIONAL")]
+    #[serde(rename_all = "camelCase")]
+    Notional {
+        #[serde(default, with = "string_or_float_opt")]
+        min_notional: Option<Number>,
+        apply_min_to_market: Option<bool>,
+        #[serde(default, with = "string_or_float_opt")]
+        max_notional: Option<Number>,
+        apply_max_to_market: Option<bool>,
+        avg_price_mins: Option<f64>,
+    },
+    #[serde(rename = "PERCENT_PRICE_BY_SIDE")]
+    #[serde(rename_all = "camelCase")]
+    PercentPriceBySide {
+        #[serde(with = "string_or_float")]
+        bid_multiplier_up: Number,
+        #[serde(with = "string_or_float")]
+        bid_multiplier_down: Number,
+        #[serde(with = "string_or_float")]
+        ask_multiplier_up: Number,
+        #[serde(with = "string_or_float")]
+        ask_multiplier_down: Number,
+        avg_price_mins: Option<f64>,
+    },
+    #[serde(rename = "TRAILING_DELTA")]
+    #[serde(rename_all = "camelCase")]
+    TrailingDelta {
+        min_trailing_above_delta: u32,
+        max_trailing_above_delta: u32,
+        min_trailing_below_delta: u32,
+        max_trailing_below_delta: u32,
+    },
     #[serde(rename = "ICEBERG_PARTS")]
     #[serde(rename_all = "camelCase")]
     IcebergParts { limit: Option<u16> },
@@ -97,10 +141,16 @@ pub enum Filters {
     #[serde(rename = "MARKET_LOT_SIZE")]
     #[serde(rename_all = "camelCase")]
     MarketLotSize {
-        min_qty: String,
-        max_qty: String,
-        step_size: String,
+        #[serde(with = "string_or_float")]
+        min_qty: Number,
+        #[serde(with = "string_or_float")]
+        max_qty: Number,
+        #[serde(with = "string_or_float")]
+        step_size: Number,
     },
+    /// A filter this version does not know, so new ones do not break the exchange information
+    #[serde(other)]
+    Unknown,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -120,8 +170,10 @@ pub struct AccountInformation {
 #[serde(rename_all = "camelCase")]
 pub struct Balance {
     pub asset: String,
-    pub free: String,
-    pub locked: String,
+    #[serde(with = "string_or_float")]
+    pub free: Number,
+    #[serde(with = "string_or_float")]
+    pub locked: Number,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -131,17 +183,20 @@ pub struct Order {
     pub order_id: u64,
     pub client_order_id: String,
     #[serde(with = "string_or_float")]
-    pub price: f64,
-    pub orig_qty: String,
-    pub executed_qty: String,
+    pub price: Number,
+    #[serde(with = "string_or_float")]
+    pub orig_qty: Number,
+    #[serde(with = "string_or_float")]
+    pub executed_qty: Number,
     pub status: String,
     pub time_in_force: String,
     #[serde(rename = "type")]
     pub type_name: String,
     pub side: String,
     #[serde(with = "string_or_float")]
-    pub stop_price: f64,
-    pub iceberg_qty: String,
+    pub stop_price: Number,
+    #[serde(with = "string_or_float")]
+    pub iceberg_qty: Number,
     pub time: u64,
 }
 
@@ -161,17 +216,22 @@ pub struct Transaction {
     pub order_id: u64,
     pub client_order_id: String,
     pub transact_time: u64,
-    #[serde(with = "string_or_float")]
-    pub price: f64,
-    #[serde(with = "string_or_float")]
-    pub orig_qty: f64,
-    #[serde(with = "string_or_float")]
-    pub executed_qty: f64,
-    #[serde(with = "string_or_float")]
-    pub cummulative_quote_qty: f64,
+    // The remaining fields are left out of ACK responses, and `fills` out of RESULT ones
+    #[serde(with = "string_or_float", default)]
+    pub price: Number,
+    #[serde(with = "string_or_float", default)]
+    pub orig_qty: Number,
+    #[serde(with = "string_or_float", default)]
+    pub executed_qty: Number,
+    #[serde(with = "string_or_float", default)]
+    pub cummulative_quote_qty: Number,
+    #[serde(default)]
     pub status: String,
+    #[serde(default)]
     pub time_in_force: String,
+    #[serde(default)]
     pub side: String,
+    #[serde(default)]
     pub fills: Vec<FillInfo>,
 }
 
@@ -179,11 +239,11 @@ pub struct Transaction {
 #[serde(rename_all = "camelCase")]
 pub struct FillInfo {
     #[serde(with = "string_or_float")]
-    pub price: f64,
+    pub price: Number,
     #[serde(with = "string_or_float")]
-    pub qty: f64,
+    pub qty: Number,
     #[serde(with = "string_or_float")]
-    pub commission: f64,
+    pub commission: Number,
     pub commission_asset: String,
     pub trade_id: Option<u64>,
 }
@@ -207,9 +267,9 @@ pub struct OrderBook {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Bids {
     #[serde(with = "string_or_float")]
-    pub price: f64,
+    pub price: Number,
     #[serde(with = "string_or_float")]
-    pub qty: f64,
+    pub qty: Number,
 
     // Never serialized.
     #[serde(skip)]
@@ -219,9 +279,9 @@ pub struct Bids {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Asks {
     #[serde(with = "string_or_float")]
-    pub price: f64,
+    pub price: Number,
     #[serde(with = "string_or_float")]
-    pub qty: f64,
+    pub qty: Number,
 
     // Never serialized.
     #[serde(skip)]
@@ -248,14 +308,14 @@ pub enum Prices {
 pub struct SymbolPrice {
     pub symbol: String,
     #[serde(with = "string_or_float")]
-    pub price: f64,
+    pub price: Number,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AveragePrice {
     pub mins: u64,
     #[serde(with = "string_or_float")]
-    pub price: f64,
+    pub price: Number,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -275,13 +335,13 @@ pub enum KlineSummaries {
 pub struct Tickers {
     pub symbol: String,
     #[serde(with = "string_or_float")]
-    pub bid_price: f64,
+    pub bid_price: Number,
     #[serde(with = "string_or_float")]
-    pub bid_qty: f64,
+    pub bid_qty: Number,
     #[serde(with = "string_or_float")]
-    pub ask_price: f64,
+    pub ask_price: Number,
     #[serde(with = "string_or_float")]
-    pub ask_qty: f64,
+    pub ask_qty: Number,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -289,10 +349,11 @@ pub struct Tickers {
 pub struct TradeHistory {
     pub id: u64,
     #[serde(with = "string_or_float")]
-    pub price: f64,
+    pub price: Number,
     #[serde(with = "string_or_float")]
-    pub qty: f64,
-    pub commission: String,
+    pub qty: Number,
+    #[serde(with = "string_or_float")]
+    pub commission: Number,
     pub commission_asset: String,
     pub time: u64,
     pub is_buyer: bool,
@@ -303,25 +364,28 @@ pub struct TradeHistory {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct PriceStats {
-    pub price_change: String,
-    pub price_change_percent: String,
-    pub weighted_avg_price: String,
     #[serde(with = "string_or_float")]
-    pub prev_close_price: f64,
+    pub price_change: Number,
+    #[serde(with = "string_or_float")]
+    pub price_change_percent: Number,
+    #[serde(with = "string_or_float")]
+    pub weighted_avg_price: Number,
     #[serde(with = "string_or_float")]
-    pub last_price: f64,
+    pub prev_close_price: Number,
     #[serde(with = "string_or_float")]
-    pub bid_price: f64,
+    pub last_price: Number,
     #[serde(with = "string_or_float")]
-    pub ask_price: f64,
+    pub bid_price: Number,
     #[serde(with = "string_or_float")]
-    pub open_price: f64,
+    pub ask_price: Number,
     #[serde(with = "string_or_float")]
-    pub high_price: f64,
+    pub open_price: Number,
     #[serde(with = "string_or_float")]
-    pub low_price: f64,
+    pub high_price: Number,
     #[serde(with = "string_or_float")]
-    pub volume: f64,
+    pub low_price: Number,
+    #[serde(with = "string_or_float")]
+    pub volume: Number,
     pub open_time: u64,
     pub close_time: u64,
     pub first_id: u64,
@@ -329,6 +393,7 @@ pub struct PriceStats {
     pub count: u64,
 }
 
+/// `outboundAccountInfo`, no longer sent by Binance in favour of `AccountPositionEvent`
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AccountUpdateEvent {
@@ -361,23 +426,105 @@ pub struct BalanceUpdateEvent {
     pub event_time: i64,
     #[serde(rename = "a")]
     pub asset: String,
-    #[serde(rename = "d")]
-    pub balance_delta: String,
+    #[serde(rename = "d", with = "string_or_float")]
+    pub balance_delta: Number,
     #[serde(rename = "T")]
     pub clear_time: i64,
 }
 
+/// Balances that changed, `outboundAccountPosition`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountPositionEvent {
+    #[serde(rename = "e")]
+    pub event_type: String,
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "u")]
+    pub last_update_time: u64,
+    #[serde(rename = "B")]
+    pub balances: Vec<EventBalance>,
+}
+
+/// A change to an order list (OCO), `listStatus`. Each of its orders gets an `executionReport` too.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ListStatusEvent {
+    #[serde(rename = "e")]
+    pub event_type: String,
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "g")]
+    pub order_list_id: i64,
+    /// e.g. `OCO`
+    #[serde(rename = "c")]
+    pub contingency_type: String,
+    /// `RESPONSE`, `EXEC_STARTED`, `UPDATED` or `ALL_DONE`
+    #[serde(rename = "l")]
+    pub list_status_type: String,
+    /// `EXECUTING`, `ALL_DONE` or `REJECT`
+    #[serde(rename = "L")]
+    pub list_order_status: String,
+    #[serde(rename = "r")]
+    pub list_reject_reason: String,
+    #[serde(rename = "C")]
+    pub list_client_order_id: String,
+    #[serde(rename = "T")]
+    pub transaction_time: u64,
+    #[serde(rename = "O")]
+    pub orders: Vec<ListStatusOrder>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ListStatusOrder {
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "i")]
+    pub order_id: u64,
+    #[serde(rename = "c")]
+    pub client_order_id: String,
+}
+
+/// Part of a spot balance locked or unlocked by another service, e.g. as margin collateral
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalLockUpdateEvent {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "a")]
+    pub asset: String,
+    #[serde(rename = "d", with = "string_or_float")]
+    pub delta: Number,
+    #[serde(rename = "T")]
+    pub transaction_time: u64,
+}
+
+/// Sent when a listen key was not kept alive in time, the user data stream stops there
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ListenKeyExpiredEvent {
+    #[serde(rename = "e")]
+    pub event_type: String,
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    pub listen_key: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct EventBalance {
     #[serde(rename = "a")]
     pub asset: String,
-    #[serde(rename = "f")]
-    pub free: String,
-    #[serde(rename = "l")]
-    pub locked: String,
+    #[serde(rename = "f", with = "string_or_float")]
+    pub free: Number,
+    #[serde(rename = "l", with = "string_or_float")]
+    pub locked: Number,
 }
 
+/// An `executionReport`, sent for every change to an order
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct OrderTradeEvent {
@@ -394,37 +541,39 @@ pub struct OrderTradeEvent {
     pub new_client_order_id: String,
 
     #[serde(rename = "S")]
-    pub side: String,
+    pub side: OrderSide,
 
     #[serde(rename = "o")]
-    pub order_type: String,
+    pub order_type: OrderType,
 
     #[serde(rename = "f")]
-    pub time_in_force: String,
+    pub time_in_force: TimeInForce,
 
-    #[serde(rename = "q")]
-    pub qty: String,
+    #[serde(rename = "q", with = "string_or_float")]
+    pub qty: Number,
 
-    #[serde(rename = "p")]
-    pub price: String,
+    #[serde(rename = "p", with = "string_or_float")]
+    pub price: Number,
 
-    #[serde(skip, rename = "P")]
-    pub p_ignore: String,
+    #[serde(rename = "P", with = "string_or_float")]
+    pub stop_price: Number,
 
-    #[serde(skip, rename = "F")]
-    pub f_ignore: String,
+    #[serde(rename = "F", with = "string_or_float")]
+    pub iceberg_qty: Number,
 
-    #[serde(skip)]
-    pub g: i32,
+    /// -1 unless the order is part of an order list (OCO)
+    #[serde(rename = "g")]
+    pub order_list_id: i64,
 
-    #[serde(skip, rename = "C")]
-    pub c_ignore: Option<String>,
+    /// Client order id of the order being canceled, empty otherwise
+    #[serde(rename = "C")]
+    pub original_client_order_id: String,
 
     #[serde(rename = "x")]
-    pub execution_type: String,
+    pub execution_type: ExecutionType,
 
     #[serde(rename = "X")]
-    pub order_status: String,
+    pub order_status: OrderStatus,
 
     #[serde(rename = "r")]
     pub order_reject_reason: String,
@@ -432,38 +581,75 @@ pub struct OrderTradeEvent {
     #[serde(rename = "i")]
     pub order_id: u64,
 
-    #[serde(rename = "l")]
-    pub qty_last_filled_trade: String,
+    #[serde(rename = "l", with = "string_or_float")]
+    pub qty_last_filled_trade: Number,
 
-    #[serde(rename = "z")]
-    pub accumulated_qty_filled_trades: String,
+    #[serde(rename = "z", with = "string_or_float")]
+    pub accumulated_qty_filled_trades: Number,
 
-    #[serde(rename = "L")]
-    pub price_last_filled_trade: String,
+    #[serde(rename = "L", with = "string_or_float")]
+    pub price_last_filled_trade: Number,
 
-    #[serde(rename = "n")]
-    pub commission: String,
+    #[serde(rename = "n", with = "string_or_float")]
+    pub commission: Number,
 
-    #[serde(skip, rename = "N")]
-    pub asset_commisioned: Option<String>,
+    /// `None` until the order traded
+    #[serde(rename = "N")]
+    pub commission_asset: Option<String>,
 
     #[serde(rename = "T")]
     pub trade_order_time: u64,
 
+    /// -1 unless the order traded
     #[serde(rename = "t")]
     pub trade_id: i64,
 
-    #[serde(skip, rename = "I")]
-    pub i_ignore: u64,
-
-    #[serde(skip)]
-    pub w: bool,
+    #[serde(rename = "w")]
+    pub is_on_book: bool,
 
     #[serde(rename = "m")]
     pub is_buyer_maker: bool,
 
-    #[serde(skip, rename = "M")]
-    pub m_ignore: bool,
+    #[serde(rename = "O")]
+    pub order_creation_time: u64,
+
+    #[serde(rename = "Z", with = "string_or_float")]
+    pub cumulative_quote_qty: Number,
+
+    #[serde(rename = "Y", with = "string_or_float")]
+    pub quote_qty_last_filled_trade: Number,
+
+    #[serde(rename = "Q", with = "string_or_float")]
+    pub quote_order_qty: Number,
+
+    /// When the order went on the book
+    #[serde(rename = "W", default)]
+    pub working_time: Option<u64>,
+
+    #[serde(rename = "V", default)]
+    pub self_trade_prevention_mode: Option<SelfTradePreventionMode>,
+
+    #[serde(rename = "d", default)]
+    pub trailing_delta: Option<u64>,
+
+    #[serde(rename = "D", default)]
+    pub trailing_time: Option<i64>,
+
+    #[serde(rename = "j", default)]
+    pub strategy_id: Option<i64>,
+
+    #[serde(rename = "J", default)]
+    pub strategy_type: Option<i64>,
+
+    /// Only sent when the order expired because of self trade prevention
+    #[serde(rename = "v", default)]
+    pub prevented_match_id: Option<u64>,
+
+    #[serde(rename = "A", default, with = "string_or_float_opt")]
+    pub prevented_qty: Option<Number>,
+
+    #[serde(rename = "B", default, with = "string_or_float_opt")]
+    pub last_prevented_qty: Option<Number>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -476,10 +662,10 @@ pub struct OneTradeEvent {
     pub symbol: String,
     #[serde(rename = "t")]
     pub trade_time: u128,
-    #[serde(rename = "p")]
-    pub price: String,
-    #[serde(rename = "q")]
-    pub qty: String,
+    #[serde(rename = "p", with = "string_or_float")]
+    pub price: Number,
+    #[serde(rename = "q", with = "string_or_float")]
+    pub qty: Number,
     pub b: i64,
     pub a: i64,
     #[serde(rename = "T")]
@@ -505,11 +691,11 @@ pub struct TradesEvent {
     #[serde(rename = "a")]
     pub aggregated_trade_id: u64,
 
-    #[serde(rename = "p")]
-    pub price: String,
+    #[serde(rename = "p", with = "string_or_float")]
+    pub price: Number,
 
-    #[serde(rename = "q")]
-    pub qty: String,
+    #[serde(rename = "q", with = "string_or_float")]
+    pub qty: Number,
 
     #[serde(rename = "f")]
     pub first_break_trade_id: u64,
@@ -536,17 +722,17 @@ pub struct BookTickerEvent {
     #[serde(rename = "s")]
     pub symbol: String,
 
-    #[serde(rename = "b")]
-    pub best_bid: String,
+    #[serde(rename = "b", with = "string_or_float")]
+    pub best_bid: Number,
 
-    #[serde(rename = "B")]
-    pub best_bid_qty: String,
+    #[serde(rename = "B", with = "string_or_float")]
+    pub best_bid_qty: Number,
 
-    #[serde(rename = "a")]
-    pub best_ask: String,
+    #[serde(rename = "a", with = "string_or_float")]
+    pub best_ask: Number,
 
-    #[serde(rename = "A")]
-    pub best_ask_qty: String,
+    #[serde(rename = "A", with = "string_or_float")]
+    pub best_ask_qty: Number,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -561,50 +747,137 @@ pub struct DayTickerEvent {
     #[serde(rename = "s")]
     pub symbol: String,
 
-    #[serde(rename = "p")]
-    pub price_change: String,
+    #[serde(rename = "p", with = "string_or_float")]
+    pub price_change: Number,
 
-    #[serde(rename = "P")]
-    pub price_change_percent: String,
+    #[serde(rename = "P", with = "string_or_float")]
+    pub price_change_percent: Number,
 
-    #[serde(rename = "w")]
-    pub average_price: String,
+    #[serde(rename = "w", with = "string_or_float")]
+    pub average_price: Number,
 
-    #[serde(rename = "x")]
-    pub prev_close: String,
+    #[serde(rename = "x", with = "string_or_float")]
+    pub prev_close: Number,
 
-    #[serde(rename = "c")]
-    pub current_close: String,
+    #[serde(rename = "c", with = "string_or_float")]
+    pub current_close: Number,
 
-    #[serde(rename = "Q")]
-    pub current_close_qty: String,
+    #[serde(rename = "Q", with = "string_or_float")]
+    pub current_close_qty: Number,
 
-    #[serde(rename = "b")]
-    pub best_bid: String,
+    #[serde(rename = "b", with = "string_or_float")]
+    pub best_bid: Number,
 
-    #[serde(rename = "B")]
-    pub best_bid_qty: String,
+    #[serde(rename = "B", with = "string_or_float")]
+    pub best_bid_qty: Number,
 
-    #[serde(rename = "a")]
-    pub best_ask: String,
+    #[serde(rename = "a", with = "string_or_float")]
+    pub best_ask: Number,
 
-    #[serde(rename = "A")]
-    pub best_ask_qty: String,
+    #[serde(rename = "A", with = "string_or_float")]
+    pub best_ask_qty: Number,
 
-    #[serde(rename = "o")]
-    pub open: String,
+    #[serde(rename = "o", with = "string_or_float")]
+    pub open: Number,
 
-    #[serde(rename = "h")]
-    pub high: String,
+    #[serde(rename = "h", with = "string_or_float")]
+    pub high: Number,
 
-    #[serde(rename = "l")]
-    pub low: String,
+    #[serde(rename = "l", with = "string_or_float")]
+    pub low: Number,
+
+    #[serde(rename = "v", with = "string_or_float")]
+    pub volume: Number,
+
+    #[serde(rename = "q", with = "string_or_float")]
+    pub quote_volume: Number,
+
+    #[serde(rename = "O")]
+    pub open_time: u64,
+
+    #[serde(rename = "C")]
+    pub close_time: u64,
+
+    #[serde(rename = "F")]
+    pub first_trade_id: i64,
+
+    #[serde(rename = "L")]
+    pub last_trade_id: i64,
+
+    #[serde(rename = "n")]
+    pub num_trades: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MiniTickerEvent {
+    #[serde(rename = "e")]
+    pub event_type: String,
+
+    #[serde(rename = "E")]
+    pub event_time: u64,
+
+    #[serde(rename = "s")]
+    pub symbol: String,
+
+    #[serde(rename = "c", with = "string_or_float")]
+    pub close: Number,
+
+    #[serde(rename = "o", with = "string_or_float")]
+    pub open: Number,
+
+    #[serde(rename = "h", with = "string_or_float")]
+    pub high: Number,
+
+    #[serde(rename = "l", with = "string_or_float")]
+    pub low: Number,
+
+    #[serde(rename = "v", with = "string_or_float")]
+    pub volume: Number,
+
+    #[serde(rename = "q", with = "string_or_float")]
+    pub quote_volume: Number,
+}
+
+/// Ticker over a rolling window of 1h, 4h or 1d, named in `event_type` (e.g. `1hTicker`)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RollingWindowTickerEvent {
+    #[serde(rename = "e")]
+    pub event_type: String,
+
+    #[serde(rename = "E")]
+    pub event_time: u64,
+
+    #[serde(rename = "s")]
+    pub symbol: String,
+
+    #[serde(rename = "p", with = "string_or_float")]
+    pub price_change: Number,
+
+    #[serde(rename = "P", with = "string_or_float")]
+    pub price_change_percent: Number,
+
+    #[serde(rename = "o", with = "string_or_float")]
+    pub open: Number,
+
+    #[serde(rename = "h", with = "string_or_float")]
+    pub high: Number,
+
+    #[serde(rename = "l", with = "string_or_float")]
+    pub low: Number,
+
+    #[serde(rename = "c", with = "string_or_float")]
+    pub last_price: Number,
 
-    #[serde(rename = "v")]
-    pub volume: String,
+    #[serde(rename = "w", with = "string_or_float")]
+    pub average_price: Number,
 
-    #[serde(rename = "q")]
-    pub quote_volume: String,
+    #[serde(rename = "v", with = "string_or_float")]
+    pub volume: Number,
+
+    #[serde(rename = "q", with = "string_or_float")]
+    pub quote_volume: Number,
 
     #[serde(rename = "O")]
     pub open_time: u64,
@@ -622,6 +895,29 @@ pub struct DayTickerEvent {
     pub num_trades: u64,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AveragePriceEvent {
+    #[serde(rename = "e")]
+    pub event_type: String,
+
+    #[serde(rename = "E")]
+    pub event_time: u64,
+
+    #[serde(rename = "s")]
+    pub symbol: String,
+
+    /// Averaged over, e.g. `5m`
+    #[serde(rename = "i")]
+    pub interval: String,
+
+    #[serde(rename = "w", with = "string_or_float")]
+    pub average_price: Number,
+
+    #[serde(rename = "T")]
+    pub last_trade_time: u64,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct KlineEvent {
@@ -642,25 +938,25 @@ pub struct KlineEvent {
 pub struct KlineSummary {
     pub open_time: i64,
 
-    pub open: f64,
+    pub open: Number,
 
-    pub high: f64,
+    pub high: Number,
 
-    pub low: f64,
+    pub low: Number,
 
-    pub close: f64,
+    pub close: Number,
 
-    pub volume: f64,
+    pub volume: Number,
 
     pub close_time: i64,
 
-    pub quote_asset_volume: f64,
+    pub quote_asset_volume: Number,
 
     pub number_of_trades: i64,
 
-    pub taker_buy_base_asset_volume: f64,
+    pub taker_buy_base_asset_volume: Number,
 
-    pub taker_buy_quote_asset_volume: f64,
+    pub taker_buy_quote_asset_volume: Number,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -684,20 +980,20 @@ pub struct Kline {
     #[serde(rename = "L")]
     pub last_trade_id: i32,
 
-    #[serde(rename = "o")]
-    pub open: String,
+    #[serde(rename = "o", with = "string_or_float")]
+    pub open: Number,
 
-    #[serde(rename = "c")]
-    pub close: String,
+    #[serde(rename = "c", with = "string_or_float")]
+    pub close: Number,
 
-    #[serde(rename = "h")]
-    pub high: String,
+    #[serde(rename = "h", with = "string_or_float")]
+    pub high: Number,
 
-    #[serde(rename = "l")]
-    pub low: String,
+    #[serde(rename = "l", with = "string_or_float")]
+    pub low: Number,
 
-    #[serde(rename = "v")]
-    pub volume: String,
+    #[serde(rename = "v", with = "string_or_float")]
+    pub volume: Number,
 
     #[serde(rename = "n")]
     pub number_of_trades: i32,
@@ -705,14 +1001,14 @@ pub struct Kline {
     #[serde(rename = "x")]
     pub is_final_bar: bool,
 
-    #[serde(rename = "q")]
-    pub quote_volume: String,
+    #[serde(rename = "q", with = "string_or_float")]
+    pub quote_volume: Number,
 
-    #[serde(rename = "V")]
-    pub active_buy_volume: String,
+    #[serde(rename = "V", with = "string_or_float")]
+    pub active_buy_volume: Number,
 
-    #[serde(rename = "Q")]
-    pub active_volume_buy_quote: String,
+    #[serde(rename = "Q", with = "string_or_float")]
+    pub active_volume_buy_quote: Number,
 
     #[serde(skip, rename = "B")]
     pub ignore_me: String,
@@ -748,6 +1044,8 @@ pub(crate) mod string_or_float {
 
     use serde::{de, Deserialize, Deserializer, Serializer};
 
+    use crate::numeric::{from_f64, Number};
+
     pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
         where
             T: fmt::Display,
@@ -756,7 +1054,7 @@ pub(crate) mod string_or_float {
         serializer.collect_str(value)
     }
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<f64, D::Error>
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Number, D::Error>
         where
             D: Deserializer<'de>,
     {
@@ -769,8 +1067,33 @@ pub(crate) mod string_or_float {
 
         match StringOrFloat::deserialize(deserializer)? {
             StringOrFloat::String(s) => s.parse().map_err(de::Error::custom),
-            StringOrFloat::Float(i) => Ok(i),
+            StringOrFloat::Float(i) => from_f64(i).ok_or_else(|| de::Error::custom(format!("{} is not a valid number", i))),
         }
     }
 }
 
+pub(crate) mod string_or_float_opt {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::numeric::Number;
+
+    pub fn serialize<S>(value: &Option<Number>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+    {
+        match value {
+            Some(value) => super::string_or_float::serialize(value, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Number>, D::Error>
+        where
+            D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Wrapper(#[serde(with = "super::string_or_float")] Number);
+
+        Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(value)| value))
+    }
+}
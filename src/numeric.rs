@@ -0,0 +1,147 @@
+//! `Number` is the type of every price and quantity in the models and order parameters.
+//!
+//! It is `f64` by default. With the `decimal` feature it is `rust_decimal::Decimal`, so
+//! balances, fills and notional values are exact and round-trip losslessly.
+
+#[cfg(feature = "decimal")]
+pub type Number = rust_decimal::Decimal;
+
+#[cfg(not(feature = "decimal"))]
+pub type Number = f64;
+
+#[cfg(feature = "decimal")]
+pub const ZERO: Number = rust_decimal::Decimal::ZERO;
+
+#[cfg(not(feature = "decimal"))]
+pub const ZERO: Number = 0.0;
+
+/// Plain notation without trailing zeros, as Binance expects in request parameters.
+/// An `f64` is cut (not rounded) to the 8 decimals Binance supports, once float noise is
+/// absorbed, so `0.1 + 0.2` is sent as `0.3` and `0.123456789` as `0.12345678`.
+/// NaN and infinities, which Binance rejects anyway, are passed through as `NaN`/`inf`.
+#[cfg(feature = "decimal")]
+pub fn to_parameter(value: Number) -> String {
+    value.normalize().to_string()
+}
+
+#[cfg(not(feature = "decimal"))]
+pub fn to_parameter(value: Number) -> String {
+    if !value.is_finite() {
+        return value.to_string();
+    }
+    // Rounding to the 15 significant digits an f64 holds first keeps e.g. 0.29 (0.28999999999999998)
+    // from being cut to 0.28999999
+    let integer_digits = if value.abs() >= 1.0 { value.abs().log10().floor() as usize + 1 } else { 0 };
+    let decimals = 15usize.saturating_sub(integer_digits).max(8);
+    let formatted = format!("{:.*}", decimals, value);
+    let formatted = &formatted[..formatted.len() - (decimals - 8)];
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "" | "-0" => "0".into(),
+        _ => trimmed.into()
+    }
+}
+
+/// Neither NaN nor infinite, which a `Decimal` never is
+#[cfg(feature = "decimal")]
+pub fn is_finite(_value: Number) -> bool {
+    true
+}
+
+#[cfg(not(feature = "decimal"))]
+pub fn is_finite(value: Number) -> bool {
+    value.is_finite()
+}
+
+/// Finite and greater than zero
+#[cfg(feature = "decimal")]
+pub fn is_positive(value: Number) -> bool {
+    value > ZERO
+}
+
+#[cfg(not(feature = "decimal"))]
+pub fn is_positive(value: Number) -> bool {
+    value.is_finite() && value > ZERO
+}
+
+#[cfg(feature = "decimal")]
+pub(crate) fn from_f64(value: f64) -> Option<Number> {
+    rust_decimal::prelude::FromPrimitive::from_f64(value)
+}
+
+#[cfg(not(feature = "decimal"))]
+pub(crate) fn from_f64(value: f64) -> Option<Number> {
+    Some(value)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    Down,
+    Up,
+    Nearest,
+}
+
+/// Round `value` to `base + n * step`, as the PRICE_FILTER and LOT_SIZE filters require
+pub fn round_to_step(value: Number, base: Number, step: Number, rounding: Rounding) -> Number {
+    let steps = snap((value - base) / step);
+    let steps = match rounding {
+        Rounding::Down => steps.floor(),
+        Rounding::Up => steps.ceil(),
+        Rounding::Nearest => steps.round(),
+    };
+    base + steps * step
+}
+
+/// Absorb the float error of a division that should have been exact, e.g. `0.3 / 0.1`
+#[cfg(not(feature = "decimal"))]
+fn snap(steps: Number) -> Number {
+    if (steps - steps.round()).abs() < 1e-9 {
+        steps.round()
+    } else {
+        steps
+    }
+}
+
+#[cfg(feature = "decimal")]
+fn snap(steps: Number) -> Number {
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::numeric::*;
+
+    #[test]
+    fn parameters_have_no_float_noise() {
+        let parse = |s: &str| s.parse::<Number>().unwrap();
+        assert_eq!(to_parameter(parse("0.1") + parse("0.2")), "0.3");
+        assert_eq!(to_parameter(parse("1.50000000")), "1.5");
+        assert_eq!(to_parameter(parse("100")), "100");
+        assert_eq!(to_parameter(parse("0.00000001")), "0.00000001");
+        assert_eq!(to_parameter(ZERO), "0");
+    }
+
+    #[test]
+    #[cfg(not(feature = "decimal"))]
+    fn parameters_are_cut_to_8_decimals() {
+        let parse = |s: &str| s.parse::<Number>().unwrap();
+        assert_eq!(to_parameter(parse("0.123456789")), "0.12345678");
+        assert_eq!(to_parameter(parse("0.29")), "0.29");
+        assert_eq!(to_parameter(parse("19000.999999999")), "19000.99999999");
+        assert_eq!(to_parameter(parse("-0.000000009")), "0");
+        assert_eq!(to_parameter(f64::NAN), "NaN");
+        assert_eq!(to_parameter(f64::INFINITY), "inf");
+        assert_eq!(to_parameter(f64::NEG_INFINITY), "-inf");
+    }
+
+    #[test]
+    fn rounds_to_the_step() {
+        let parse = |s: &str| s.parse::<Number>().unwrap();
+        let round = |value, rounding| to_parameter(round_to_step(parse(value), parse("0.01"), parse("0.1"), rounding));
+        assert_eq!(round("0.31", Rounding::Down), "0.31");
+        assert_eq!(round("0.3", Rounding::Down), "0.21");
+        assert_eq!(round("0.3", Rounding::Up), "0.31");
+        assert_eq!(round("0.34", Rounding::Nearest), "0.31");
+        assert_eq!(to_parameter(round_to_step(parse("0.3"), ZERO, parse("0.1"), Rounding::Down)), "0.3");
+    }
+}
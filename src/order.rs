@@ -0,0 +1,731 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{BinanceErr, FilterFailure};
+use crate::model::Filters;
+use crate::numeric::{is_positive, round_to_step, to_parameter, Number, Rounding, ZERO};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrderSide {
+    Buy,
+    Sell,
+    /// Sent by Binance, but unknown to this version
+    #[serde(other)]
+    Unknown,
+}
+
+impl OrderSide {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderSide::Buy => "BUY",
+            OrderSide::Sell => "SELL",
+            OrderSide::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrderType {
+    Limit,
+    Market,
+    StopLoss,
+    StopLossLimit,
+    TakeProfit,
+    TakeProfitLimit,
+    LimitMaker,
+    /// Sent by Binance, but unknown to this version
+    #[serde(other)]
+    Unknown,
+}
+
+impl OrderType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderType::Limit => "LIMIT",
+            OrderType::Market => "MARKET",
+            OrderType::StopLoss => "STOP_LOSS",
+            OrderType::StopLossLimit => "STOP_LOSS_LIMIT",
+            OrderType::TakeProfit => "TAKE_PROFIT",
+            OrderType::TakeProfitLimit => "TAKE_PROFIT_LIMIT",
+            OrderType::LimitMaker => "LIMIT_MAKER",
+            OrderType::Unknown => "UNKNOWN",
+        }
+    }
+
+    /// Rests on the book at `price`
+    pub fn has_limit_price(&self) -> bool {
+        matches!(self, OrderType::Limit | OrderType::StopLossLimit | OrderType::TakeProfitLimit | OrderType::LimitMaker)
+    }
+
+    /// Triggered by `stopPrice` or `trailingDelta`
+    pub fn is_conditional(&self) -> bool {
+        matches!(self, OrderType::StopLoss | OrderType::StopLossLimit | OrderType::TakeProfit | OrderType::TakeProfitLimit)
+    }
+
+    /// Takes a `timeInForce`; LIMIT_MAKER never does, it is rejected instead of taking liquidity
+    pub fn requires_time_in_force(&self) -> bool {
+        matches!(self, OrderType::Limit | OrderType::StopLossLimit | OrderType::TakeProfitLimit)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TimeInForce {
+    /// Good til canceled
+    Gtc,
+    /// Immediate or cancel
+    Ioc,
+    /// Fill or kill
+    Fok,
+    /// Sent by Binance, but unknown to this version
+    #[serde(other)]
+    Unknown,
+}
+
+impl TimeInForce {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimeInForce::Gtc => "GTC",
+            TimeInForce::Ioc => "IOC",
+            TimeInForce::Fok => "FOK",
+            TimeInForce::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SelfTradePreventionMode {
+    None,
+    ExpireTaker,
+    ExpireMaker,
+    ExpireBoth,
+    Decrement,
+    /// Expire the taker, and send the quantity it would have traded to the maker's account
+    Transfer,
+    /// Sent by Binance, but unknown to this version
+    #[serde(other)]
+    Unknown,
+}
+
+impl SelfTradePreventionMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SelfTradePreventionMode::None => "NONE",
+            SelfTradePreventionMode::ExpireTaker => "EXPIRE_TAKER",
+            SelfTradePreventionMode::ExpireMaker => "EXPIRE_MAKER",
+            SelfTradePreventionMode::ExpireBoth => "EXPIRE_BOTH",
+            SelfTradePreventionMode::Decrement => "DECREMENT",
+            SelfTradePreventionMode::Transfer => "TRANSFER",
+            SelfTradePreventionMode::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+/// How much of the order Binance sends back. MARKET and LIMIT default to `Full`, other types to `Ack`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum NewOrderRespType {
+    Ack,
+    Result,
+    Full,
+}
+
+impl NewOrderRespType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NewOrderRespType::Ack => "ACK",
+            NewOrderRespType::Result => "RESULT",
+            NewOrderRespType::Full => "FULL",
+        }
+    }
+}
+
+/// What happened to an order, the `x` of an `executionReport`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ExecutionType {
+    New,
+    Canceled,
+    /// Amended or canceled and replaced
+    Replaced,
+    Rejected,
+    Trade,
+    Expired,
+    /// Expired by self trade prevention
+    TradePrevention,
+    /// Sent by Binance, but unknown to this version
+    #[serde(other)]
+    Unknown,
+}
+
+impl ExecutionType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExecutionType::New => "NEW",
+            ExecutionType::Canceled => "CANCELED",
+            ExecutionType::Replaced => "REPLACED",
+            ExecutionType::Rejected => "REJECTED",
+            ExecutionType::Trade => "TRADE",
+            ExecutionType::Expired => "EXPIRED",
+            ExecutionType::TradePrevention => "TRADE_PREVENTION",
+            ExecutionType::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrderStatus {
+    /// An order of a list waiting for the one it depends on
+    PendingNew,
+    New,
+    PartiallyFilled,
+    Filled,
+    Canceled,
+    PendingCancel,
+    Rejected,
+    Expired,
+    /// Expired by self trade prevention
+    ExpiredInMatch,
+    /// Sent by Binance, but unknown to this version
+    #[serde(other)]
+    Unknown,
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::PendingNew => "PENDING_NEW",
+            OrderStatus::New => "NEW",
+            OrderStatus::PartiallyFilled => "PARTIALLY_FILLED",
+            OrderStatus::Filled => "FILLED",
+            OrderStatus::Canceled => "CANCELED",
+            OrderStatus::PendingCancel => "PENDING_CANCEL",
+            OrderStatus::Rejected => "REJECTED",
+            OrderStatus::Expired => "EXPIRED",
+            OrderStatus::ExpiredInMatch => "EXPIRED_IN_MATCH",
+            OrderStatus::Unknown => "UNKNOWN",
+        }
+    }
+
+    /// Nothing more will happen to the order
+    pub fn is_final(&self) -> bool {
+        matches!(self, OrderStatus::Filled | OrderStatus::Canceled | OrderStatus::Rejected | OrderStatus::Expired | OrderStatus::ExpiredInMatch)
+    }
+}
+
+macro_rules! display_as_str {
+    ($($name:ident),*) => {
+        $(impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        })*
+    };
+}
+
+display_as_str!(OrderSide, OrderType, TimeInForce, SelfTradePreventionMode, NewOrderRespType, ExecutionType, OrderStatus);
+
+/// A new spot order, for `Account::place_order` and `Account::test_order`.
+///
+/// The parameters each order type requires or forbids are checked by `validate` before
+/// anything is sent. Setting a `newClientOrderId` also lets the client resend the order
+/// safely after an ambiguous failure.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewOrder {
+    pub symbol: String,
+    pub side: OrderSide,
+    pub order_type: OrderType,
+    pub time_in_force: Option<TimeInForce>,
+    pub quantity: Option<Number>,
+    pub quote_order_qty: Option<Number>,
+    pub price: Option<Number>,
+    pub stop_price: Option<Number>,
+    pub iceberg_qty: Option<Number>,
+    pub new_client_order_id: Option<String>,
+    pub trailing_delta: Option<u32>,
+    pub self_trade_prevention_mode: Option<SelfTradePreventionMode>,
+    pub new_order_resp_type: Option<NewOrderRespType>,
+}
+
+impl NewOrder {
+    pub fn new<S: Into<String>>(symbol: S, side: OrderSide, order_type: OrderType) -> Self {
+        NewOrder {
+            symbol: symbol.into(),
+            side,
+            order_type,
+            time_in_force: None,
+            quantity: None,
+            quote_order_qty: None,
+            price: None,
+            stop_price: None,
+            iceberg_qty: None,
+            new_client_order_id: None,
+            trailing_delta: None,
+            self_trade_prevention_mode: None,
+            new_order_resp_type: None,
+        }
+    }
+
+    /// Good til canceled LIMIT order
+    pub fn limit<S: Into<String>>(symbol: S, side: OrderSide, quantity: Number, price: Number) -> Self {
+        NewOrder::new(symbol, side, OrderType::Limit)
+            .set_quantity(quantity)
+            .set_price(price)
+            .set_time_in_force(TimeInForce::Gtc)
+    }
+
+    /// MARKET order for `quantity` of the base asset
+    pub fn market<S: Into<String>>(symbol: S, side: OrderSide, quantity: Number) -> Self {
+        NewOrder::new(symbol, side, OrderType::Market).set_quantity(quantity)
+    }
+
+    pub fn set_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = Some(time_in_force);
+        self
+    }
+
+    pub fn set_quantity(mut self, quantity: Number) -> Self {
+        self.quantity = Some(quantity);
+        self
+    }
+
+    /// MARKET only: spend (BUY) or receive (SELL) this much of the quote asset instead of a base `quantity`
+    pub fn set_quote_order_qty(mut self, quote_order_qty: Number) -> Self {
+        self.quote_order_qty = Some(quote_order_qty);
+        self
+    }
+
+    pub fn set_price(mut self, price: Number) -> Self {
+        self.price = Some(price);
+        self
+    }
+
+    pub fn set_stop_price(mut self, stop_price: Number) -> Self {
+        self.stop_price = Some(stop_price);
+        self
+    }
+
+    pub fn set_iceberg_qty(mut self, iceberg_qty: Number) -> Self {
+        self.iceberg_qty = Some(iceberg_qty);
+        self
+    }
+
+    pub fn set_new_client_order_id<S: Into<String>>(mut self, new_client_order_id: S) -> Self {
+        self.new_client_order_id = Some(new_client_order_id.into());
+        self
+    }
+
+    /// Trailing stop distance in basis points (BIPS)
+    pub fn set_trailing_delta(mut self, trailing_delta: u32) -> Self {
+        self.trailing_delta = Some(trailing_delta);
+        self
+    }
+
+    pub fn set_self_trade_prevention_mode(mut self, mode: SelfTradePreventionMode) -> Self {
+        self.self_trade_prevention_mode = Some(mode);
+        self
+    }
+
+    pub fn set_new_order_resp_type(mut self, resp_type: NewOrderRespType) -> Self {
+        self.new_order_resp_type = Some(resp_type);
+        self
+    }
+
+    /// Check the parameter combination against the rules of `order_type`
+    pub fn validate(&self) -> Result<(), BinanceErr> {
+        let order_type = self.order_type;
+        let invalid = |msg: String| Err(BinanceErr::InvalidOrder(msg));
+
+        if self.symbol.is_empty() {
+            return invalid("symbol is empty".into());
+        }
+        // Only ever parsed from Binance, there is nothing to send
+        let unknown = self.side == OrderSide::Unknown
+            || order_type == OrderType::Unknown
+            || self.time_in_force == Some(TimeInForce::Unknown)
+            || self.self_trade_prevention_mode == Some(SelfTradePreventionMode::Unknown);
+        if unknown {
+            return invalid("side, type, timeInForce and selfTradePreventionMode cannot be UNKNOWN".into());
+        }
+        let amounts = [
+            ("quantity", self.quantity),
+            ("quoteOrderQty", self.quote_order_qty),
+            ("price", self.price),
+            ("stopPrice", self.stop_price),
+            ("icebergQty", self.iceberg_qty),
+        ];
+        for (name, value) in amounts.iter() {
+            if let Some(value) = value {
+                if !is_positive(*value) {
+                    return invalid(format!("{} must be positive, got {}", name, value));
+                }
+            }
+        }
+
+        match (order_type, self.quantity.is_some(), self.quote_order_qty.is_some()) {
+            (OrderType::Market, true, true) => return invalid("MARKET takes either quantity or quoteOrderQty, not both".into()),
+            (OrderType::Market, false, false) => return invalid("MARKET requires quantity or quoteOrderQty".into()),
+            (OrderType::Market, _, _) => {}
+            (_, _, true) => return invalid(format!("quoteOrderQty is only valid for MARKET, not {}", order_type)),
+            (_, false, _) => return invalid(format!("{} requires quantity", order_type)),
+            _ => {}
+        }
+
+        match (order_type.has_limit_price(), self.price.is_some()) {
+            (true, false) => return invalid(format!("{} requires price", order_type)),
+            (false, true) => return invalid(format!("price is not valid for {}", order_type)),
+            _ => {}
+        }
+
+        let triggered = self.stop_price.is_some() || self.trailing_delta.is_some();
+        match (order_type.is_conditional(), triggered) {
+            (true, false) => return invalid(format!("{} requires stopPrice or trailingDelta", order_type)),
+            (false, true) => return invalid(format!("stopPrice and trailingDelta are not valid for {}", order_type)),
+            _ => {}
+        }
+        if self.trailing_delta == Some(0) {
+            return invalid("trailingDelta must be positive".into());
+        }
+
+        match (order_type.requires_time_in_force(), self.time_in_force) {
+            (true, None) => return invalid(format!("{} requires timeInForce", order_type)),
+            (false, Some(_)) => return invalid(format!("timeInForce is not valid for {}", order_type)),
+            _ => {}
+        }
+
+        if let Some(iceberg_qty) = self.iceberg_qty {
+            if !order_type.has_limit_price() {
+                return invalid(format!("icebergQty is not valid for {}", order_type));
+            }
+            if self.time_in_force.is_some_and(|tif| tif != TimeInForce::Gtc) {
+                return invalid("icebergQty requires timeInForce GTC".into());
+            }
+            if self.quantity.is_some_and(|quantity| iceberg_qty >= quantity) {
+                return invalid("icebergQty must be less than quantity".into());
+            }
+        }
+
+        if let Some(id) = &self.new_client_order_id {
+            let allowed = |c: char| c.is_ascii_alphanumeric() || ".:/_-".contains(c);
+            if id.is_empty() || id.len() > 36 || !id.chars().all(allowed) {
+                return invalid(format!("newClientOrderId must match ^[.A-Z:/a-z0-9_-]{{1,36}}$, got {:?}", id));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Round the order to the symbol `filters` and check it against them, so an order Binance
+    /// would reject with "Filter failure: ..." never costs request weight.
+    ///
+    /// Quantities are rounded down to the step size. Prices are rounded to the tick size away
+    /// from the market (down for a BUY, up for a SELL), so the order is never worse than asked.
+    /// `average_price` (e.g. `Market::get_average_price`) enables the PERCENT_PRICE(_BY_SIDE) checks
+    /// and the MIN_NOTIONAL/NOTIONAL checks of MARKET orders; they are skipped without it.
+    pub fn apply_filters(mut self, filters: &[Filters], average_price: Option<Number>) -> Result<NewOrder, BinanceErr> {
+        let price_rounding = match self.side {
+            OrderSide::Buy => Rounding::Down,
+            OrderSide::Sell => Rounding::Up,
+            OrderSide::Unknown => return self.validate().map(|_| self),
+        };
+
+        for filter in filters {
+            match filter {
+                Filters::PriceFilter { min_price, max_price, tick_size } => {
+                    for price in [&mut self.price, &mut self.stop_price].iter_mut() {
+                        let price = match price {
+                            Some(price) => price,
+                            None => continue
+                        };
+                        if *tick_size > ZERO {
+                            *price = round_to_step(*price, *min_price, *tick_size, price_rounding);
+                        }
+                        if *min_price > ZERO && *price < *min_price {
+                            return violation(FilterFailure::PriceFilter, format!("price {} is below the minimum {}", price, min_price));
+                        }
+                        if *max_price > ZERO && *price > *max_price {
+                            return violation(FilterFailure::PriceFilter, format!("price {} is above the maximum {}", price, max_price));
+                        }
+                    }
+                }
+                Filters::LotSize { min_qty, max_qty, step_size } => {
+                    for quantity in [&mut self.quantity, &mut self.iceberg_qty].iter_mut().flat_map(|q| q.as_mut()) {
+                        check_lot_size(FilterFailure::LotSize, quantity, *min_qty, *max_qty, *step_size)?;
+                    }
+                }
+                Filters::MarketLotSize { min_qty, max_qty, step_size } if self.order_type == OrderType::Market => {
+                    if let Some(quantity) = &mut self.quantity {
+                        check_lot_size(FilterFailure::MarketLotSize, quantity, *min_qty, *max_qty, *step_size)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Checked once every amount is rounded
+        for filter in filters {
+            match filter {
+                Filters::PercentPrice { multiplier_up, multiplier_down, .. } => {
+                    let (price, average_price) = match (self.price, average_price) {
+                        (Some(price), Some(average_price)) => (price, average_price),
+                        _ => continue
+                    };
+                    let (low, high) = (average_price * *multiplier_down, average_price * *multiplier_up);
+                    if price < low || price > high {
+                        return violation(FilterFailure::PercentPrice, format!("price {} is outside of {} - {}", price, low, high));
+                    }
+                }
+                Filters::PercentPriceBySide { bid_multiplier_up, bid_multiplier_down, ask_multiplier_up, ask_multiplier_down, .. } => {
+                    let (price, average_price) = match (self.price, average_price) {
+                        (Some(price), Some(average_price)) => (price, average_price),
+                        _ => continue
+                    };
+                    let (multiplier_down, multiplier_up) = match self.side {
+                        OrderSide::Buy => (bid_multiplier_down, bid_multiplier_up),
+                        OrderSide::Sell | OrderSide::Unknown => (ask_multiplier_down, ask_multiplier_up),
+                    };
+                    let (low, high) = (average_price * *multiplier_down, average_price * *multiplier_up);
+                    if price < low || price > high {
+                        return violation(FilterFailure::PercentPriceBySide, format!("price {} is outside of {} - {}", price, low, high));
+                    }
+                }
+                Filters::MinNotional { min_notional: Some(min_notional), apply_to_market, .. } => {
+                    if let Some(notional) = self.notional(average_price, *apply_to_market) {
+                        if notional < *min_notional {
+                            return violation(FilterFailure::MinNotional, format!("notional {} is below the minimum {}", notional, min_notional));
+                        }
+                    }
+                }
+                Filters::Notional { min_notional, apply_min_to_market, max_notional, apply_max_to_market, .. } => {
+                    if let (Some(min_notional), Some(notional)) = (min_notional, self.notional(average_price, *apply_min_to_market)) {
+                        if notional < *min_notional {
+                            return violation(FilterFailure::Notional, format!("notional {} is below the minimum {}", notional, min_notional));
+                        }
+                    }
+                    if let (Some(max_notional), Some(notional)) = (max_notional, self.notional(average_price, *apply_max_to_market)) {
+                        if notional > *max_notional {
+                            return violation(FilterFailure::Notional, format!("notional {} is above the maximum {}", notional, max_notional));
+                        }
+                    }
+                }
+                Filters::TrailingDelta { min_trailing_above_delta, max_trailing_above_delta, min_trailing_below_delta, max_trailing_below_delta } => {
+                    let trailing_delta = match self.trailing_delta {
+                        Some(trailing_delta) => trailing_delta,
+                        None => continue
+                    };
+                    // The stop of a BUY STOP_LOSS or a SELL TAKE_PROFIT trails above the market
+                    let above = matches!(
+                        (self.side, self.order_type),
+                        (OrderSide::Buy, OrderType::StopLoss)
+                            | (OrderSide::Buy, OrderType::StopLossLimit)
+                            | (OrderSide::Sell, OrderType::TakeProfit)
+                            | (OrderSide::Sell, OrderType::TakeProfitLimit)
+                    );
+                    let (min, max) = if above {
+                        (*min_trailing_above_delta, *max_trailing_above_delta)
+                    } else {
+                        (*min_trailing_below_delta, *max_trailing_below_delta)
+                    };
+                    if trailing_delta < min || trailing_delta > max {
+                        return violation(FilterFailure::TrailingDelta, format!("trailing delta {} is outside of {} - {}", trailing_delta, min, max));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Value of the order in the quote asset. MARKET orders are valued at `average_price`,
+    /// unless the filter does not apply to them.
+    fn notional(&self, average_price: Option<Number>, applies_to_market: Option<bool>) -> Option<Number> {
+        match (self.order_type, self.quote_order_qty) {
+            (OrderType::Market, _) if applies_to_market == Some(false) => None,
+            (OrderType::Market, Some(quote_order_qty)) => Some(quote_order_qty),
+            (OrderType::Market, None) => average_price.zip(self.quantity).map(|(p, q)| p * q),
+            _ => self.price.zip(self.quantity).map(|(p, q)| p * q),
+        }
+    }
+
+    /// Request parameters, without the `recvWindow`/`timestamp` the client adds when signing
+    pub(crate) fn to_parameters(&self) -> BTreeMap<String, String> {
+        let mut parameters: BTreeMap<String, String> = BTreeMap::new();
+        parameters.insert("symbol".into(), self.symbol.clone());
+        parameters.insert("side".into(), self.side.to_string());
+        parameters.insert("type".into(), self.order_type.to_string());
+
+        let optional = [
+            ("timeInForce", self.time_in_force.map(|v| v.to_string())),
+            ("quantity", self.quantity.map(to_parameter)),
+            ("quoteOrderQty", self.quote_order_qty.map(to_parameter)),
+            ("price", self.price.map(to_parameter)),
+            ("stopPrice", self.stop_price.map(to_parameter)),
+            ("icebergQty", self.iceberg_qty.map(to_parameter)),
+            ("newClientOrderId", self.new_client_order_id.clone()),
+            ("trailingDelta", self.trailing_delta.map(|v| v.to_string())),
+            ("selfTradePreventionMode", self.self_trade_prevention_mode.map(|v| v.to_string())),
+            ("newOrderRespType", self.new_order_resp_type.map(|v| v.to_string())),
+        ];
+        for (name, value) in optional.iter() {
+            if let Some(value) = value {
+                parameters.insert((*name).into(), value.clone());
+            }
+        }
+        parameters
+    }
+}
+
+fn check_lot_size(filter: FilterFailure, quantity: &mut Number, min_qty: Number, max_qty: Number, step_size: Number) -> Result<(), BinanceErr> {
+    if step_size > ZERO {
+        *quantity = round_to_step(*quantity, min_qty, step_size, Rounding::Down);
+    }
+    if *quantity < min_qty || *quantity <= ZERO {
+        return violation(filter, format!("quantity {} is below the minimum {}", quantity, min_qty));
+    }
+    if max_qty > ZERO && *quantity > max_qty {
+        return violation(filter, format!("quantity {} is above the maximum {}", quantity, max_qty));
+    }
+    Ok(())
+}
+
+fn violation<T>(filter: FilterFailure, msg: String) -> Result<T, BinanceErr> {
+    Err(BinanceErr::FilterViolation { filter, msg })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::order::*;
+
+    fn n(s: &str) -> Number {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parameters_follow_the_order_type() {
+        let order = NewOrder::new("BTCUSDT", OrderSide::Sell, OrderType::StopLossLimit)
+            .set_quantity(n("0.5"))
+            .set_price(n("19000"))
+            .set_stop_price(n("19500"))
+            .set_time_in_force(TimeInForce::Gtc)
+            .set_new_client_order_id("my_order-1")
+            .set_self_trade_prevention_mode(SelfTradePreventionMode::ExpireMaker);
+        assert!(order.validate().is_ok());
+
+        let parameters = order.to_parameters();
+        assert_eq!(parameters["type"], "STOP_LOSS_LIMIT");
+        assert_eq!(parameters["stopPrice"], "19500");
+        assert_eq!(parameters["selfTradePreventionMode"], "EXPIRE_MAKER");
+        assert!(!parameters.contains_key("quoteOrderQty"));
+    }
+
+    #[test]
+    fn rejects_invalid_combinations() {
+        let invalid = [
+            NewOrder::new("BTCUSDT", OrderSide::Buy, OrderType::Limit).set_quantity(n("1")).set_price(n("10")),
+            NewOrder::market("BTCUSDT", OrderSide::Buy, n("1")).set_quote_order_qty(n("10")),
+            NewOrder::market("BTCUSDT", OrderSide::Buy, n("1")).set_price(n("10")),
+            NewOrder::new("BTCUSDT", OrderSide::Buy, OrderType::StopLoss).set_quantity(n("1")),
+            NewOrder::new("BTCUSDT", OrderSide::Buy, OrderType::LimitMaker)
+                .set_quantity(n("1"))
+                .set_price(n("10"))
+                .set_time_in_force(TimeInForce::Gtc),
+            NewOrder::limit("BTCUSDT", OrderSide::Buy, n("1"), n("10")).set_iceberg_qty(n("2")),
+            NewOrder::limit("BTCUSDT", OrderSide::Buy, n("-1"), n("10")),
+            NewOrder::limit("BTCUSDT", OrderSide::Buy, n("1"), n("10")).set_new_client_order_id("not allowed!"),
+        ];
+        for order in invalid.iter() {
+            assert!(matches!(order.validate(), Err(BinanceErr::InvalidOrder(_))), "{:?}", order);
+        }
+    }
+
+    #[test]
+    fn values_added_by_binance_later_are_unknown() {
+        let parse = |value: &str| serde_json::Value::from(value);
+        assert_eq!(serde_json::from_value::<SelfTradePreventionMode>(parse("TRANSFER")).unwrap(), SelfTradePreventionMode::Transfer);
+        assert_eq!(serde_json::from_value::<OrderSide>(parse("SOMETHING_NEW")).unwrap(), OrderSide::Unknown);
+        assert_eq!(serde_json::from_value::<OrderType>(parse("SOMETHING_NEW")).unwrap(), OrderType::Unknown);
+        assert_eq!(serde_json::from_value::<TimeInForce>(parse("GTD")).unwrap(), TimeInForce::Unknown);
+        assert_eq!(serde_json::from_value::<SelfTradePreventionMode>(parse("SOMETHING_NEW")).unwrap(), SelfTradePreventionMode::Unknown);
+        assert_eq!(serde_json::from_value::<ExecutionType>(parse("SOMETHING_NEW")).unwrap(), ExecutionType::Unknown);
+        assert_eq!(serde_json::from_value::<OrderStatus>(parse("SOMETHING_NEW")).unwrap(), OrderStatus::Unknown);
+
+        let order = NewOrder::limit("BTCUSDT", OrderSide::Buy, n("1"), n("10")).set_time_in_force(TimeInForce::Unknown);
+        assert!(matches!(order.validate(), Err(BinanceErr::InvalidOrder(_))));
+    }
+
+    #[test]
+    fn rounds_and_checks_against_the_symbol_filters() {
+        let filters = vec![
+            Filters::PriceFilter { min_price: n("0.01"), max_price: n("1000000"), tick_size: n("0.01") },
+            Filters::LotSize { min_qty: n("0.00001"), max_qty: n("9000"), step_size: n("0.00001") },
+            Filters::MinNotional { min_notional: Some(n("10")), apply_to_market: Some(true), avg_price_mins: None },
+            Filters::PercentPrice { multiplier_up: n("5"), multiplier_down: n("0.2"), avg_price_mins: None },
+        ];
+
+        let order = NewOrder::limit("BTCUSDT", OrderSide::Buy, n("0.123456"), n("20000.009"))
+            .apply_filters(&filters, Some(n("20000")))
+            .unwrap();
+        let parameters = order.to_parameters();
+        assert_eq!(parameters["quantity"], "0.12345");
+        assert_eq!(parameters["price"], "20000");
+
+        let sell = NewOrder::limit("BTCUSDT", OrderSide::Sell, n("1"), n("20000.001")).apply_filters(&filters, None);
+        assert_eq!(sell.unwrap().to_parameters()["price"], "20000.01");
+
+        let failures = [
+            (NewOrder::limit("BTCUSDT", OrderSide::Buy, n("0.000001"), n("20000")), FilterFailure::LotSize),
+            (NewOrder::limit("BTCUSDT", OrderSide::Buy, n("0.0001"), n("20000")), FilterFailure::MinNotional),
+            (NewOrder::limit("BTCUSDT", OrderSide::Buy, n("1"), n("200000")), FilterFailure::PercentPrice),
+            (NewOrder::market("BTCUSDT", OrderSide::Buy, n("0.0001")), FilterFailure::MinNotional),
+        ];
+        for (order, expected) in failures.iter() {
+            let error = order.clone().apply_filters(&filters, Some(n("20000"))).unwrap_err();
+            assert_eq!(error.filter_failure().as_ref(), Some(expected), "{:?}", order);
+        }
+    }
+
+    #[test]
+    fn checks_the_filters_that_replaced_min_notional() {
+        let filters: Vec<Filters> = serde_json::from_value(serde_json::json!([
+            { "filterType": "PERCENT_PRICE_BY_SIDE", "bidMultiplierUp": "1.2", "bidMultiplierDown": "0.2", "askMultiplierUp": "5", "askMultiplierDown": "0.8", "avgPriceMins": 5 },
+            { "filterType": "NOTIONAL", "minNotional": "5", "applyMinToMarket": false, "maxNotional": "100000", "applyMaxToMarket": true, "avgPriceMins": 5 },
+            { "filterType": "TRAILING_DELTA", "minTrailingAboveDelta": 10, "maxTrailingAboveDelta": 2000, "minTrailingBelowDelta": 20, "maxTrailingBelowDelta": 1000 },
+            { "filterType": "SOME_FUTURE_FILTER", "limit": 1 }
+        ])).unwrap();
+        assert!(matches!(filters[3], Filters::Unknown));
+
+        let stop = |side, trailing_delta| NewOrder::new("BTCUSDT", side, OrderType::StopLoss).set_quantity(n("1")).set_trailing_delta(trailing_delta);
+        let passing = [
+            NewOrder::limit("BTCUSDT", OrderSide::Buy, n("1"), n("20000")),
+            NewOrder::limit("BTCUSDT", OrderSide::Sell, n("1"), n("90000")),
+            // Too small, but the minimum does not apply to MARKET orders
+            NewOrder::market("BTCUSDT", OrderSide::Buy, n("0.0001")),
+            stop(OrderSide::Buy, 10),
+            stop(OrderSide::Sell, 1000),
+        ];
+        for order in passing.iter() {
+            assert!(order.clone().apply_filters(&filters, Some(n("20000"))).is_ok(), "{:?}", order);
+        }
+
+        let failures = [
+            (NewOrder::limit("BTCUSDT", OrderSide::Buy, n("1"), n("30000")), FilterFailure::PercentPriceBySide),
+            (NewOrder::limit("BTCUSDT", OrderSide::Sell, n("1"), n("15000")), FilterFailure::PercentPriceBySide),
+            (NewOrder::limit("BTCUSDT", OrderSide::Buy, n("0.0001"), n("20000")), FilterFailure::Notional),
+            (NewOrder::market("BTCUSDT", OrderSide::Buy, n("10")), FilterFailure::Notional),
+            (stop(OrderSide::Buy, 5), FilterFailure::TrailingDelta),
+            (stop(OrderSide::Sell, 1500), FilterFailure::TrailingDelta),
+        ];
+        for (order, expected) in failures.iter() {
+            let error = order.clone().apply_filters(&filters, Some(n("20000"))).unwrap_err();
+            assert_eq!(error.filter_failure().as_ref(), Some(expected), "{:?}", order);
+        }
+    }
+}
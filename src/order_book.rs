@@ -0,0 +1,554 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::stream::SelectAll;
+use futures::{Stream, StreamExt};
+use tokio::sync::{mpsc, oneshot, watch};
+use uuid::Uuid;
+
+use crate::error::BinanceErr;
+use crate::market::Market;
+use crate::model::{DepthOrderBookEvent, OrderBook};
+use crate::numeric::{Number, ZERO};
+use crate::order::OrderSide;
+use crate::websocket::{
+    SubscriptionEvent, SubscriptionStream, UpdateSpeed, Websocket, WebsocketAsync, WebsocketEvent, WebsocketStreamType,
+};
+
+/// Price of a level, ordered even though `Number` may be an `f64`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Price(Number);
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.partial_cmp(&other.0).unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Order book of one symbol, as maintained by an `OrderBookManager`
+#[derive(Debug, Clone, Default)]
+pub struct LocalOrderBook {
+    pub symbol: String,
+    /// Last update applied, from the snapshot or the `u` of a diff
+    pub last_update_id: u64,
+    bids: BTreeMap<Price, Number>,
+    asks: BTreeMap<Price, Number>,
+}
+
+impl LocalOrderBook {
+    fn from_snapshot(symbol: &str, snapshot: OrderBook) -> Self {
+        LocalOrderBook {
+            symbol: symbol.into(),
+            last_update_id: snapshot.last_update_id,
+            bids: snapshot.bids.into_iter().map(|level| (Price(level.price), level.qty)).collect(),
+            asks: snapshot.asks.into_iter().map(|level| (Price(level.price), level.qty)).collect(),
+        }
+    }
+
+    /// A quantity of zero removes the level
+    fn apply(&mut self, event: &DepthOrderBookEvent) {
+        let bids = event.bids.iter().map(|level| (level.price, level.qty));
+        let asks = event.asks.iter().map(|level| (level.price, level.qty));
+        for (levels, updates) in [(&mut self.bids, bids.collect::<Vec<_>>()), (&mut self.asks, asks.collect())].iter_mut() {
+            for (price, qty) in updates.iter() {
+                if *qty == ZERO {
+                    levels.remove(&Price(*price));
+                } else {
+                    levels.insert(Price(*price), *qty);
+                }
+            }
+        }
+        self.last_update_id = event.final_update_id;
+    }
+
+    /// Highest bid, as (price, quantity)
+    pub fn best_bid(&self) -> Option<(Number, Number)> {
+        self.bids.iter().next_back().map(|(price, qty)| (price.0, *qty))
+    }
+
+    /// Lowest ask, as (price, quantity)
+    pub fn best_ask(&self) -> Option<(Number, Number)> {
+        self.asks.iter().next().map(|(price, qty)| (price.0, *qty))
+    }
+
+    /// Best `levels` bids, highest first
+    pub fn bids(&self, levels: usize) -> Vec<(Number, Number)> {
+        self.bids.iter().rev().take(levels).map(|(price, qty)| (price.0, *qty)).collect()
+    }
+
+    /// Best `levels` asks, lowest first
+    pub fn asks(&self, levels: usize) -> Vec<(Number, Number)> {
+        self.asks.iter().take(levels).map(|(price, qty)| (price.0, *qty)).collect()
+    }
+
+    /// Average price of a market order of `size`: a BUY walks up the asks, a SELL down the bids.
+    /// `None` if the book is not deep enough to fill it, or the side is unknown.
+    pub fn vwap(&self, side: OrderSide, size: Number) -> Option<Number> {
+        if size <= ZERO {
+            return None;
+        }
+        let levels = match side {
+            OrderSide::Buy => self.asks(usize::MAX),
+            OrderSide::Sell => self.bids(usize::MAX),
+            OrderSide::Unknown => return None,
+        };
+        let (mut remaining, mut cost) = (size, ZERO);
+        for (price, qty) in levels {
+            let filled = if qty < remaining { qty } else { remaining };
+            cost += price * filled;
+            remaining -= filled;
+            if remaining <= ZERO {
+                return Some(cost / size);
+            }
+        }
+        None
+    }
+}
+
+/// Synced books of one symbol, skipping the moments it is out of sync
+pub type OrderBookUpdates = Pin<Box<dyn Stream<Item = Arc<LocalOrderBook>> + Send>>;
+
+type BookReceiver = watch::Receiver<Option<Arc<LocalOrderBook>>>;
+
+type Reply = oneshot::Sender<Result<BookReceiver, BinanceErr>>;
+
+enum ManagerCommand {
+    Track { symbol: String, reply: Reply },
+    Untrack { symbol: String },
+}
+
+/// Keeps local order books in sync from the diff depth streams and REST snapshots, following
+/// https://github.com/binance/binance-spot-api-docs/blob/master/web-socket-streams.md#how-to-manage-a-local-order-book-correctly
+///
+/// Diffs are buffered while the snapshot is fetched, the ones it already contains are dropped,
+/// and the book is resynced from a new snapshot whenever a gap in the update ids shows up
+/// (or the connection was reconnected). Every symbol shares the same websocket connection.
+/// A book whose subscription gave up reconnecting is untracked, ending its updates.
+#[derive(Clone)]
+pub struct OrderBookManager {
+    commands: mpsc::UnboundedSender<ManagerCommand>,
+    books: Arc<Mutex<HashMap<String, BookReceiver>>>,
+}
+
+impl OrderBookManager {
+    /// Diffs every 100ms and snapshots of 1000 levels
+    pub fn new(market: Market, websocket: Websocket) -> Self {
+        OrderBookManager::new_with_settings(market, websocket, UpdateSpeed::Ms100, 1000)
+    }
+
+    /// `snapshot_limit` levels per side are fetched, up to 5000
+    pub fn new_with_settings(market: Market, websocket: Websocket, speed: UpdateSpeed, snapshot_limit: u16) -> Self {
+        let (commands, receiver) = mpsc::unbounded_channel();
+        let books = Arc::new(Mutex::new(HashMap::new()));
+        let driver = Driver {
+            market,
+            websocket: Arc::new(tokio::sync::Mutex::new(websocket)),
+            events: SelectAll::new(),
+            speed,
+            snapshot_limit,
+            books: HashMap::new(),
+            receivers: books.clone(),
+        };
+        tokio::spawn(driver.run(receiver));
+        OrderBookManager { commands, books }
+    }
+
+    /// Start maintaining the book of `symbol`, if it is not already
+    pub async fn track(&self, symbol: &str) -> Result<OrderBookUpdates, BinanceErr> {
+        let symbol = symbol.to_uppercase();
+        let (reply, response) = oneshot::channel();
+        self.commands
+            .send(ManagerCommand::Track { symbol: symbol.clone(), reply })
+            .map_err(|_| BinanceErr::from_str("Order book manager stopped".into()))?;
+        let receiver = response
+            .await
+            .map_err(|_| BinanceErr::from_str("Order book manager stopped".into()))??;
+        self.lock().insert(symbol, receiver.clone());
+        Ok(updates(receiver))
+    }
+
+    /// Stop maintaining the book of `symbol` and unsubscribe from its diffs
+    pub fn untrack(&self, symbol: &str) {
+        let symbol = symbol.to_uppercase();
+        self.lock().remove(&symbol);
+        self.commands.send(ManagerCommand::Untrack { symbol }).ok();
+    }
+
+    /// Current book of `symbol`, `None` if it is not tracked or not in sync right now
+    pub fn book(&self, symbol: &str) -> Option<Arc<LocalOrderBook>> {
+        self.lock().get(&symbol.to_uppercase()).and_then(|receiver| receiver.borrow().clone())
+    }
+
+    /// Every synced book of `symbol` from now on, until it is untracked
+    pub fn updates(&self, symbol: &str) -> Option<OrderBookUpdates> {
+        self.lock().get(&symbol.to_uppercase()).cloned().map(updates)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, BookReceiver>> {
+        self.books.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn updates(receiver: BookReceiver) -> OrderBookUpdates {
+    Box::pin(futures::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            receiver.changed().await.ok()?;
+            let book = receiver.borrow_and_update().clone();
+            if let Some(book) = book {
+                return Some((book, receiver));
+            }
+        }
+    }))
+}
+
+enum SyncState {
+    /// Waiting for a snapshot, keeping the diffs received meanwhile
+    Buffering(Vec<DepthOrderBookEvent>),
+    /// Snapshot applied, the next diff has to straddle its update id
+    Bridging,
+    Synced,
+}
+
+struct TrackedBook {
+    /// `None` until the diff depth stream is subscribed to
+    subscription: Option<Uuid>,
+    /// `track` calls waiting for the subscription
+    waiting: Vec<Reply>,
+    book: LocalOrderBook,
+    state: SyncState,
+    published: watch::Sender<Option<Arc<LocalOrderBook>>>,
+}
+
+impl TrackedBook {
+    /// `false` if the buffered diffs do not follow the snapshot and another one is needed
+    fn apply_snapshot(&mut self, snapshot: OrderBook) -> bool {
+        let buffered = match std::mem::replace(&mut self.state, SyncState::Bridging) {
+            SyncState::Buffering(buffered) => buffered,
+            // Already synced by an earlier snapshot
+            state => {
+                self.state = state;
+                return true;
+            }
+        };
+        self.book = LocalOrderBook::from_snapshot(&self.book.symbol, snapshot);
+
+        let mut buffered = buffered.into_iter();
+        let in_sync = buffered.by_ref().all(|event| self.step(event));
+        match &mut self.state {
+            SyncState::Buffering(kept) => kept.extend(buffered),
+            _ => self.publish(),
+        }
+        in_sync
+    }
+
+    /// `false` if the diff does not follow the last one and the book has to be resynced
+    fn apply_diff(&mut self, event: DepthOrderBookEvent) -> bool {
+        let in_sync = self.step(event);
+        if !matches!(self.state, SyncState::Buffering(_)) {
+            self.publish();
+        }
+        in_sync
+    }
+
+    fn step(&mut self, event: DepthOrderBookEvent) -> bool {
+        let next = self.book.last_update_id + 1;
+        let in_sequence = match &mut self.state {
+            SyncState::Buffering(buffered) => {
+                buffered.push(event);
+                return true;
+            }
+            // Already part of the book
+            _ if event.final_update_id < next => return true,
+            SyncState::Bridging => event.first_update_id <= next,
+            SyncState::Synced => event.first_update_id == next,
+        };
+        if in_sequence {
+            self.book.apply(&event);
+            self.state = SyncState::Synced;
+        } else {
+            // Kept, the next snapshot may well be older than it
+            self.state = SyncState::Buffering(vec![event]);
+            self.published.send_replace(None);
+        }
+        in_sequence
+    }
+
+    fn publish(&self) {
+        self.published.send_replace(Some(Arc::new(self.book.clone())));
+    }
+
+    fn resync(&mut self) {
+        self.state = SyncState::Buffering(Vec::new());
+        self.published.send_replace(None);
+    }
+}
+
+/// Events of a tracked subscription, then `None` once it ended
+type BookEvents = Pin<Box<dyn Stream<Item = (Uuid, Option<Result<SubscriptionEvent, BinanceErr>>)> + Send>>;
+
+/// Handles the diffs and the commands one at a time. Subscribing and unsubscribing wait for
+/// Binance, so they run in the background and never hold up the diffs of the other books.
+struct Driver {
+    market: Market,
+    websocket: Arc<tokio::sync::Mutex<Websocket>>,
+    /// The diffs of every tracked symbol
+    events: SelectAll<BookEvents>,
+    speed: UpdateSpeed,
+    snapshot_limit: u16,
+    books: HashMap<String, TrackedBook>,
+    /// Shared with the `OrderBookManager`
+    receivers: Arc<Mutex<HashMap<String, BookReceiver>>>,
+}
+
+type Snapshots = mpsc::UnboundedSender<(String, Result<OrderBook, BinanceErr>)>;
+type Subscriptions = mpsc::UnboundedSender<(String, Result<SubscriptionStream, BinanceErr>)>;
+
+impl Driver {
+    async fn run(mut self, mut commands: mpsc::UnboundedReceiver<ManagerCommand>) {
+        let (snapshots_sender, mut snapshots) = mpsc::unbounded_channel();
+        let (subscriptions_sender, mut subscriptions) = mpsc::unbounded_channel();
+        loop {
+            tokio::select! {
+                command = commands.recv() => match command {
+                    Some(ManagerCommand::Track { symbol, reply }) => self.track(symbol, reply, &subscriptions_sender),
+                    Some(ManagerCommand::Untrack { symbol }) => {
+                        if let Some(subscription) = self.books.remove(&symbol).and_then(|tracked| tracked.subscription) {
+                            self.unsubscribe(subscription);
+                        }
+                    }
+                    // Every `OrderBookManager` is gone
+                    None => return,
+                },
+                Some((symbol, subscribed)) = subscriptions.recv() => self.subscribed(symbol, subscribed, &snapshots_sender),
+                // An empty `SelectAll` ends right away
+                event = self.events.next(), if !self.events.is_empty() => match event {
+                    Some((_, Some(Ok(event)))) => match event.event {
+                        WebsocketEvent::DiffDepthStream(event) => {
+                            let symbol = event.symbol.clone();
+                            // `Option::is_none_or` would need Rust 1.82
+                            #[allow(clippy::unnecessary_map_or)]
+                            let in_sync = self.books.get_mut(&symbol).map_or(true, |tracked| tracked.apply_diff(event));
+                            if !in_sync {
+                                self.request_snapshot(&symbol, &snapshots_sender, Duration::ZERO);
+                            }
+                        }
+                        // Diffs are missed until it is reconnected
+                        WebsocketEvent::Disconnected { subscription, .. } => {
+                            if let Some((_, tracked)) = self.tracked_mut(subscription) {
+                                tracked.resync();
+                            }
+                        }
+                        WebsocketEvent::Reconnected { subscription } => {
+                            if let Some((symbol, tracked)) = self.tracked_mut(subscription) {
+                                tracked.resync();
+                                let symbol = symbol.clone();
+                                self.request_snapshot(&symbol, &snapshots_sender, Duration::ZERO);
+                            }
+                        }
+                        _ => {}
+                    },
+                    // Reconnecting was given up, or the book was untracked
+                    Some((subscription, None)) => self.forget(subscription),
+                    _ => {}
+                },
+                Some((symbol, snapshot)) = snapshots.recv() => {
+                    let synced = match (self.books.get_mut(&symbol), snapshot) {
+                        (Some(tracked), Ok(snapshot)) => tracked.apply_snapshot(snapshot),
+                        (Some(_), Err(_)) => false,
+                        // Untracked meanwhile
+                        (None, _) => true,
+                    };
+                    if !synced {
+                        self.request_snapshot(&symbol, &snapshots_sender, Duration::from_secs(1));
+                    }
+                }
+            }
+        }
+    }
+
+    /// `reply` is answered once the diff depth stream is subscribed to
+    fn track(&mut self, symbol: String, reply: Reply, subscriptions: &Subscriptions) {
+        if let Some(tracked) = self.books.get_mut(&symbol) {
+            match tracked.subscription {
+                Some(_) => {
+                    reply.send(Ok(tracked.published.subscribe())).ok();
+                }
+                None => tracked.waiting.push(reply),
+            }
+            return;
+        }
+        let (published, _) = watch::channel(None);
+        let book = LocalOrderBook { symbol: symbol.clone(), ..LocalOrderBook::default() };
+        let state = SyncState::Buffering(Vec::new());
+        self.books.insert(symbol.clone(), TrackedBook { subscription: None, waiting: vec![reply], book, state, published });
+
+        let stream_type = WebsocketStreamType::DiffDepthStream { symbols: vec![symbol.clone()], speed: self.speed };
+        let (websocket, subscriptions) = (self.websocket.clone(), subscriptions.clone());
+        tokio::spawn(async move {
+            let subscribed = websocket.lock().await.subscribe(stream_type).await;
+            subscriptions.send((symbol, subscribed)).ok();
+        });
+    }
+
+    fn subscribed(&mut self, symbol: String, subscribed: Result<SubscriptionStream, BinanceErr>, snapshots: &Snapshots) {
+        let awaited = self.books.get(&symbol).is_some_and(|tracked| tracked.subscription.is_none());
+        if !awaited {
+            // Untracked meanwhile
+            if let Ok(events) = subscribed {
+                self.unsubscribe(events.id());
+            }
+            return;
+        }
+        let events = match subscribed {
+            Ok(events) => events,
+            Err(e) => {
+                let mut waiting = self.books.remove(&symbol).map(|tracked| tracked.waiting).unwrap_or_default().into_iter();
+                let reason = e.to_string();
+                if let Some(reply) = waiting.next() {
+                    reply.send(Err(e)).ok();
+                }
+                for reply in waiting {
+                    reply.send(Err(BinanceErr::from_str(reason.clone()))).ok();
+                }
+                return;
+            }
+        };
+        let subscription = events.id();
+        if let Some(tracked) = self.books.get_mut(&symbol) {
+            tracked.subscription = Some(subscription);
+            for reply in tracked.waiting.drain(..) {
+                reply.send(Ok(tracked.published.subscribe())).ok();
+            }
+        }
+        let ended = futures::stream::once(async move { (subscription, None) });
+        self.events.push(Box::pin(events.map(move |event| (subscription, Some(event))).chain(ended)));
+        self.request_snapshot(&symbol, snapshots, Duration::ZERO);
+    }
+
+    fn unsubscribe(&self, subscription: Uuid) {
+        let websocket = self.websocket.clone();
+        tokio::spawn(async move {
+            websocket.lock().await.unsubscribe(subscription).await.ok();
+        });
+    }
+
+    fn tracked_mut(&mut self, subscription: Uuid) -> Option<(&String, &mut TrackedBook)> {
+        self.books.iter_mut().find(|(_, tracked)| tracked.subscription == Some(subscription))
+    }
+
+    /// Untrack the book of a subscription that ended, its `updates` end with it
+    fn forget(&mut self, subscription: Uuid) {
+        let symbol = match self.tracked_mut(subscription) {
+            Some((symbol, _)) => symbol.clone(),
+            None => return
+        };
+        if let Some(tracked) = self.books.remove(&symbol) {
+            tracked.published.send_replace(None);
+        }
+        self.receivers.lock().unwrap_or_else(|e| e.into_inner()).remove(&symbol);
+    }
+
+    /// Fetched in the background, diffs keep being buffered meanwhile
+    fn request_snapshot(&self, symbol: &str, snapshots: &Snapshots, delay: Duration) {
+        let (market, symbol, snapshots, limit) = (self.market.clone(), symbol.to_string(), snapshots.clone(), self.snapshot_limit);
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let snapshot = market.get_custom_depth(symbol.as_str(), limit).await;
+            snapshots.send((symbol, snapshot)).ok();
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Method;
+    use serde_json::json;
+
+    use crate::api::Binance;
+    use crate::mock_server::MockBinanceServer;
+    use crate::order_book::*;
+    use crate::retry::RetryPolicy;
+
+    fn n(s: &str) -> Number {
+        s.parse().unwrap()
+    }
+
+    fn diff(first: u64, last: u64, bids: serde_json::Value, asks: serde_json::Value) -> serde_json::Value {
+        json!({ "e": "depthUpdate", "E": 1, "s": "BTCUSDT", "U": first, "u": last, "b": bids, "a": asks })
+    }
+
+    #[tokio::test]
+    async fn syncs_from_snapshot_and_diffs_and_resyncs_on_gaps() {
+        let server = MockBinanceServer::start().await.unwrap();
+        server.once(Method::GET, "/api/v3/depth", 200, r#"{"lastUpdateId":100,"bids":[["99.0","1"],["98.0","2"]],"asks":[["101.0","1"],["102.0","3"]]}"#);
+        server.once(Method::GET, "/api/v3/depth", 200, r#"{"lastUpdateId":300,"bids":[["97.0","1"]],"asks":[["103.0","1"]]}"#);
+        let config = server.config();
+        let manager = OrderBookManager::new(Market::new_with_config(None, None, &config), Websocket::new_with_config(&config));
+
+        let mut updates = manager.track("btcusdt").await.unwrap();
+        server.wait_for_subscription("btcusdt@depth@100ms").await;
+        server.push_event("btcusdt@depth@100ms", diff(95, 99, json!([["99.0", "5"]]), json!([])));
+        server.push_event("btcusdt@depth@100ms", diff(100, 102, json!([["99.0", "0"], ["99.5", "4"]]), json!([])));
+        server.push_event("btcusdt@depth@100ms", diff(103, 103, json!([]), json!([["101.0", "0"]])));
+
+        let book = loop {
+            let book = updates.next().await.unwrap();
+            if book.last_update_id == 103 {
+                break book;
+            }
+        };
+        assert_eq!(book.best_bid(), Some((n("99.5"), n("4"))));
+        assert_eq!(book.best_ask(), Some((n("102.0"), n("3"))));
+        assert_eq!(book.bids(5), vec![(n("99.5"), n("4")), (n("98.0"), n("2"))]);
+        assert_eq!(book.vwap(OrderSide::Sell, n("6")), Some((n("99.5") * n("4") + n("98.0") * n("2")) / n("6")));
+        assert_eq!(book.vwap(OrderSide::Buy, n("4")), None);
+
+        // 104 never arrived, so the book is rebuilt from a new snapshot
+        server.push_event("btcusdt@depth@100ms", diff(200, 301, json!([]), json!([])));
+        server.push_event("btcusdt@depth@100ms", diff(302, 302, json!([["97.0", "2"]]), json!([])));
+        let book = loop {
+            let book = updates.next().await.unwrap();
+            if book.last_update_id == 302 {
+                break book;
+            }
+        };
+        assert_eq!(book.best_bid(), Some((n("97.0"), n("2"))));
+        assert_eq!(manager.book("BTCUSDT").unwrap().last_update_id, 302);
+        assert_eq!(server.requests().iter().filter(|r| r.path() == "/api/v3/depth").count(), 2);
+    }
+
+    #[tokio::test]
+    async fn books_are_out_of_sync_while_disconnected_and_untracked_once_reconnecting_fails() {
+        let server = MockBinanceServer::start().await.unwrap();
+        server.on(Method::GET, "/api/v3/depth", 200, r#"{"lastUpdateId":100,"bids":[["99.0","1"]],"asks":[["101.0","1"]]}"#);
+        let reconnect_policy = RetryPolicy { max_attempts: 1, initial_backoff: Duration::from_millis(200), ..RetryPolicy::reconnect() };
+        let config = server.config().set_reconnect_policy(reconnect_policy);
+        let manager = OrderBookManager::new(Market::new_with_config(None, None, &config), Websocket::new_with_config(&config));
+
+        // Tracking two symbols at once, neither waits for the other
+        let (btc, eth) = tokio::join!(manager.track("BTCUSDT"), manager.track("ETHBTC"));
+        let (mut updates, _) = (btc.unwrap(), eth.unwrap());
+        server.wait_for_subscription("btcusdt@depth@100ms").await;
+        server.push_event("btcusdt@depth@100ms", diff(100, 101, json!([["99.0", "2"]]), json!([])));
+        while updates.next().await.unwrap().last_update_id != 101 {}
+
+        server.reject_subscriptions(true);
+        server.disconnect_websockets();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(manager.book("BTCUSDT").is_none(), "a disconnected book is out of sync");
+
+        assert!(tokio::time::timeout(Duration::from_secs(5), updates.next()).await.unwrap().is_none());
+        assert!(manager.updates("BTCUSDT").is_none());
+    }
+}
@@ -0,0 +1,426 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use reqwest::header::HeaderMap;
+
+use crate::error::BinanceErr;
+use crate::model::RateLimit;
+
+const USED_WEIGHT_HEADER: &str = "x-mbx-used-weight-";
+const ORDER_COUNT_HEADER: &str = "x-mbx-order-count-";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateLimitType {
+    RequestWeight,
+    Orders,
+    RawRequests,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateLimitInterval {
+    Second,
+    Minute,
+    Hour,
+    Day,
+}
+
+impl RateLimitInterval {
+    fn from_letter(letter: &str) -> Option<Self> {
+        match letter {
+            "s" | "S" => Some(RateLimitInterval::Second),
+            "m" | "M" => Some(RateLimitInterval::Minute),
+            "h" | "H" => Some(RateLimitInterval::Hour),
+            "d" | "D" => Some(RateLimitInterval::Day),
+            _ => None
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "SECOND" => Some(RateLimitInterval::Second),
+            "MINUTE" => Some(RateLimitInterval::Minute),
+            "HOUR" => Some(RateLimitInterval::Hour),
+            "DAY" => Some(RateLimitInterval::Day),
+            _ => None
+        }
+    }
+
+    fn as_millis(&self) -> u64 {
+        match self {
+            RateLimitInterval::Second => 1_000,
+            RateLimitInterval::Minute => 60_000,
+            RateLimitInterval::Hour => 3_600_000,
+            RateLimitInterval::Day => 86_400_000,
+        }
+    }
+}
+
+/// What the limiter does when the next request would go over a known limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RateLimitPolicy {
+    /// Wait for the window to roll over, then send
+    #[default]
+    Delay,
+    /// Fail fast with `BinanceErr::RateLimited`
+    Error,
+    /// Only track usage, never hold a request back
+    Disabled,
+}
+
+/// Usage of one rate limit window as last reported by Binance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitUsage {
+    pub limit_type: RateLimitType,
+    pub interval: RateLimitInterval,
+    pub interval_num: u16,
+    pub used: u64,
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct WindowKey {
+    limit_type: RateLimitType,
+    interval: RateLimitInterval,
+    interval_num: u16,
+}
+
+impl WindowKey {
+    fn length_millis(&self) -> u64 {
+        self.interval.as_millis() * u64::from(self.interval_num.max(1))
+    }
+
+    /// Binance windows are aligned on the clock, e.g. a 1 minute window resets on the minute
+    fn index_at(&self, now_millis: u64) -> u64 {
+        now_millis / self.length_millis()
+    }
+
+    fn until_next(&self, now_millis: u64) -> Duration {
+        let length = self.length_millis();
+        Duration::from_millis(length - now_millis % length)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Window {
+    limit: Option<u64>,
+    used: u64,
+    index: u64,
+    /// Reserved by requests sent but not answered yet, which Binance has not reported
+    in_flight: u64,
+}
+
+impl Window {
+    /// What a request of `cost` would bring the window to, in the window `index`
+    fn used_with(&self, index: u64, cost: u64) -> u64 {
+        let used = if self.index == index { self.used } else { 0 };
+        used + self.in_flight + cost
+    }
+}
+
+#[derive(Debug, Default)]
+struct RateLimiterState {
+    windows: HashMap<WindowKey, Window>,
+    banned_until: Option<Instant>,
+}
+
+/// Tracks request weight and order counts from the `X-MBX-USED-WEIGHT-*` and
+/// `X-MBX-ORDER-COUNT-*` response headers, and holds requests back before Binance
+/// starts answering 429/418. Clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    policy: RateLimitPolicy,
+    state: Arc<Mutex<RateLimiterState>>,
+}
+
+impl RateLimiter {
+    pub fn new(policy: RateLimitPolicy) -> Self {
+        RateLimiter {
+            policy,
+            state: Arc::new(Mutex::new(RateLimiterState::default())),
+        }
+    }
+
+    pub fn policy(&self) -> RateLimitPolicy {
+        self.policy
+    }
+
+    /// Set the limits from `ExchangeInformation::rate_limits`
+    pub fn seed(&self, rate_limits: &[RateLimit]) {
+        let mut state = self.lock();
+        for rate_limit in rate_limits {
+            let limit_type = match rate_limit.rate_limit_type.as_str() {
+                "REQUEST_WEIGHT" => RateLimitType::RequestWeight,
+                "ORDERS" => RateLimitType::Orders,
+                "RAW_REQUESTS" => RateLimitType::RawRequests,
+                _ => continue
+            };
+            if let Some(interval) = RateLimitInterval::from_name(&rate_limit.interval) {
+                let key = WindowKey { limit_type, interval, interval_num: rate_limit.interval_num };
+                state.windows.entry(key).or_default().limit = Some(rate_limit.limit);
+            }
+        }
+    }
+
+    /// Current usage of every window seen in headers or seeded from exchange info
+    pub fn usage(&self) -> Vec<RateLimitUsage> {
+        let now = now_millis();
+        self.lock()
+            .windows
+            .iter()
+            .map(|(key, window)| RateLimitUsage {
+                limit_type: key.limit_type,
+                interval: key.interval,
+                interval_num: key.interval_num,
+                used: if window.index == key.index_at(now) { window.used } else { 0 },
+                limit: window.limit,
+            })
+            .collect()
+    }
+
+    /// Wait (or fail, depending on the policy) until a request of `weight` fits in every window,
+    /// and reserve it there. Concurrent callers see each other's reservations, which are released
+    /// when the permit is dropped, once the response has reported the usage.
+    pub async fn acquire(&self, endpoint: &str, weight: u64, is_order: bool) -> Result<RateLimitPermit, BinanceErr> {
+        if self.policy == RateLimitPolicy::Disabled {
+            return Ok(RateLimitPermit { limiter: self.clone(), reserved: Vec::new() });
+        }
+        loop {
+            match self.try_reserve(weight, is_order) {
+                Ok(reserved) => return Ok(RateLimitPermit { limiter: self.clone(), reserved }),
+                Err(wait) if self.policy == RateLimitPolicy::Error => {
+                    return Err(BinanceErr::RateLimited { retry_after: wait, status: None, endpoint: endpoint.into() });
+                }
+                Err(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+
+    /// Record the usage Binance reported for the last request
+    pub fn update_from_headers(&self, headers: &HeaderMap) {
+        let now = now_millis();
+        let mut state = self.lock();
+        for (name, value) in headers {
+            let name = name.as_str();
+            let (limit_type, suffix) = if let Some(suffix) = name.strip_prefix(USED_WEIGHT_HEADER) {
+                (RateLimitType::RequestWeight, suffix)
+            } else if let Some(suffix) = name.strip_prefix(ORDER_COUNT_HEADER) {
+                (RateLimitType::Orders, suffix)
+            } else {
+                continue;
+            };
+            let used = value.to_str().ok().and_then(|v| v.parse::<u64>().ok());
+            if let (Some(key), Some(used)) = (parse_window(limit_type, suffix), used) {
+                let window = state.windows.entry(key).or_default();
+                window.used = used;
+                window.index = key.index_at(now);
+            }
+        }
+    }
+
+    /// Stop sending anything until `retry_after` has elapsed, after a 429 or 418
+    // `Option::is_none_or` would need Rust 1.82
+    #[allow(clippy::unnecessary_map_or)]
+    pub fn back_off(&self, retry_after: Duration) {
+        let until = Instant::now() + retry_after;
+        let mut state = self.lock();
+        if state.banned_until.map_or(true, |banned_until| banned_until < until) {
+            state.banned_until = Some(until);
+        }
+    }
+
+    /// Reserve the cost of a request in every window it counts towards, or tell how long to wait
+    fn try_reserve(&self, weight: u64, is_order: bool) -> Result<Vec<(WindowKey, u64)>, Duration> {
+        let now = now_millis();
+        let mut state = self.lock();
+        let banned = state
+            .banned_until
+            .and_then(|banned_until| banned_until.checked_duration_since(Instant::now()));
+        let costs: Vec<(WindowKey, u64)> = state
+            .windows
+            .keys()
+            .filter_map(|key| match key.limit_type {
+                RateLimitType::RequestWeight => Some((*key, weight)),
+                RateLimitType::RawRequests => Some((*key, 1)),
+                RateLimitType::Orders if is_order => Some((*key, 1)),
+                RateLimitType::Orders => None,
+            })
+            .collect();
+        let wait = costs
+            .iter()
+            .filter_map(|(key, cost)| {
+                let window = &state.windows[key];
+                let index = key.index_at(now);
+                match window.limit {
+                    // A request heavier than the whole limit still goes through in an empty window
+                    Some(limit) if window.used_with(index, *cost) > limit && window.used_with(index, 0) > 0 => {
+                        Some(key.until_next(now))
+                    }
+                    _ => None
+                }
+            })
+            .chain(banned)
+            .max();
+        if let Some(wait) = wait {
+            return Err(wait);
+        }
+        for (key, cost) in &costs {
+            if let Some(window) = state.windows.get_mut(key) {
+                window.in_flight += cost;
+            }
+        }
+        Ok(costs)
+    }
+
+    fn release(&self, reserved: &[(WindowKey, u64)]) {
+        let mut state = self.lock();
+        for (key, cost) in reserved {
+            if let Some(window) = state.windows.get_mut(key) {
+                window.in_flight = window.in_flight.saturating_sub(*cost);
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RateLimiterState> {
+        // The state is only ever a set of counters, keep going after a poisoning panic
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The cost of one request reserved by `RateLimiter::acquire`, released on drop
+#[derive(Debug)]
+pub struct RateLimitPermit {
+    limiter: RateLimiter,
+    reserved: Vec<(WindowKey, u64)>,
+}
+
+impl Drop for RateLimitPermit {
+    fn drop(&mut self) {
+        self.limiter.release(&self.reserved);
+    }
+}
+
+/// Request weight of the spot endpoints, from the Binance documentation. Anything not listed
+/// (futures included) is counted as 1, the headers of the response correct the estimate.
+pub fn request_weight(endpoint: &str, query: &str) -> u64 {
+    let param = |name: &str| {
+        query.split('&').find_map(|pair| pair.strip_prefix(name).and_then(|rest| rest.strip_prefix('=')))
+    };
+    let has_symbol = param("symbol").is_some() || param("symbols").is_some();
+    match endpoint {
+        "/api/v3/depth" => match param("limit").and_then(|limit| limit.parse::<u64>().ok()).unwrap_or(100) {
+            0..=100 => 5,
+            101..=500 => 25,
+            501..=1000 => 50,
+            _ => 250,
+        },
+        "/api/v3/exchangeInfo" | "/api/v3/account" | "/api/v3/allOrders" | "/api/v3/myTrades" => 20,
+        "/api/v3/trades" | "/api/v3/historicalTrades" => 25,
+        "/api/v3/ticker/24hr" if has_symbol => 2,
+        "/api/v3/ticker/24hr" => 80,
+        "/api/v3/openOrders" if has_symbol => 6,
+        "/api/v3/openOrders" => 80,
+        "/api/v3/ticker/price" | "/api/v3/ticker/bookTicker" if has_symbol => 2,
+        "/api/v3/ticker/price" | "/api/v3/ticker/bookTicker" => 4,
+        "/api/v3/klines" | "/api/v3/aggTrades" | "/api/v3/avgPrice" | "/api/v3/userDataStream" => 2,
+        _ => 1,
+    }
+}
+
+/// `1m` -> (Minute, 1), `10s` -> (Second, 10)
+fn parse_window(limit_type: RateLimitType, suffix: &str) -> Option<WindowKey> {
+    if suffix.len() < 2 {
+        return None;
+    }
+    let (num, letter) = suffix.split_at(suffix.len() - 1);
+    Some(WindowKey {
+        limit_type,
+        interval: RateLimitInterval::from_letter(letter)?,
+        interval_num: num.parse().ok()?,
+    })
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    use crate::api::Binance;
+    use crate::binance_futures::general::FuturesGeneral;
+    use crate::binance_futures::market::FuturesMarket;
+    use crate::client::Client;
+    use crate::config::Config;
+    use crate::model::RateLimit;
+    use crate::rate_limit::*;
+
+    #[test]
+    fn tracks_headers_against_seeded_limits() {
+        let limiter = RateLimiter::new(RateLimitPolicy::Error);
+        limiter.seed(&[RateLimit {
+            rate_limit_type: "REQUEST_WEIGHT".into(),
+            interval: "MINUTE".into(),
+            interval_num: 1,
+            limit: 1200,
+        }]);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-mbx-used-weight-1m", HeaderValue::from_static("1200"));
+        headers.insert("x-mbx-order-count-10s", HeaderValue::from_static("3"));
+        limiter.update_from_headers(&headers);
+
+        let usage = limiter.usage();
+        let weight = usage.iter().find(|u| u.limit_type == RateLimitType::RequestWeight).unwrap();
+        assert_eq!((weight.used, weight.limit), (1200, Some(1200)));
+        let orders = usage.iter().find(|u| u.limit_type == RateLimitType::Orders).unwrap();
+        assert_eq!((orders.interval, orders.interval_num, orders.used), (RateLimitInterval::Second, 10, 3));
+
+        // At the limit, so the next request is held back until the minute rolls over
+        assert!(limiter.try_reserve(1, false).is_err());
+    }
+
+    #[tokio::test]
+    async fn reserves_the_weight_of_requests_in_flight() {
+        let limiter = RateLimiter::new(RateLimitPolicy::Error);
+        limiter.seed(&[RateLimit {
+            rate_limit_type: "REQUEST_WEIGHT".into(),
+            interval: "MINUTE".into(),
+            interval_num: 1,
+            limit: 30,
+        }]);
+
+        let weight = request_weight("/api/v3/exchangeInfo", "");
+        assert_eq!(weight, 20);
+        let in_flight = limiter.acquire("/api/v3/exchangeInfo", weight, false).await.unwrap();
+        assert!(limiter.acquire("/api/v3/exchangeInfo", weight, false).await.unwrap_err().is_rate_limited());
+        let small = limiter.acquire("/api/v3/ping", 1, false).await.unwrap();
+
+        drop(in_flight);
+        drop(small);
+        assert!(limiter.acquire("/api/v3/exchangeInfo", weight, false).await.is_ok());
+        assert_eq!(request_weight("/api/v3/depth", "limit=1000&symbol=BTCUSDT"), 50);
+        assert_eq!(request_weight("/api/v3/openOrders", "recvWindow=5000&timestamp=1"), 80);
+    }
+
+    #[test]
+    fn handles_for_the_same_host_share_a_limiter() {
+        let config = Config::default();
+        let client = Client::new_with_config(None, None, config.rest_api_endpoint.clone(), &config);
+        let general = FuturesGeneral::new_with_client(&client, &config);
+        let market = FuturesMarket::new_with_client(&client, &config);
+        general.client.rate_limiter().seed(&[RateLimit {
+            rate_limit_type: "REQUEST_WEIGHT".into(),
+            interval: "MINUTE".into(),
+            interval_num: 1,
+            limit: 2400,
+        }]);
+
+        assert_eq!(market.client.rate_limiter().usage().len(), 1);
+        assert!(client.rate_limiter().usage().is_empty(), "the spot host is limited separately");
+    }
+}
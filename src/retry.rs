@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+/// How a `Client` retries requests that failed for a transient reason
+/// (network errors, 5xx, 429/418).
+///
+/// Requests that may have changed state on the exchange (placing or cancelling orders)
+/// are only resent when the first attempt provably never reached the matching engine.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first one. `1` disables retrying.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+    /// Randomise every backoff between zero and its computed value ("full jitter")
+    pub jitter: bool,
+    /// A `Retry-After` longer than this is returned to the caller instead of waited on
+    pub max_retry_after: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2.0,
+            jitter: true,
+            max_retry_after: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// Websocket reconnection: never gives up, backing off from half a second up to 30 seconds
+    pub fn reconnect() -> Self {
+        RetryPolicy {
+            max_attempts: u32::MAX,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// Backoff before the given retry, `attempt` starting at 1 for the first retry
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(32) as i32;
+        let backoff = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+        let backoff = Duration::from_secs_f64(backoff.min(self.max_backoff.as_secs_f64()));
+        if self.jitter {
+            backoff.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+        } else {
+            backoff
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use tokio::task::JoinHandle;
+
+use crate::error::BinanceErr;
+use crate::general::General;
+use crate::model::{ExchangeInformation, Filters, Symbol};
+use crate::numeric::{to_parameter, Number};
+use crate::order::NewOrder;
+
+#[derive(Debug, Default)]
+struct RegistryState {
+    symbols: HashMap<String, Arc<Symbol>>,
+    by_base_asset: HashMap<String, Vec<String>>,
+    by_quote_asset: HashMap<String, Vec<String>>,
+    refreshed_at: Option<Instant>,
+}
+
+impl RegistryState {
+    fn from_exchange_info(info: ExchangeInformation) -> Self {
+        let mut state = RegistryState { refreshed_at: Some(Instant::now()), ..RegistryState::default() };
+        for symbol in info.symbols {
+            state.by_base_asset.entry(symbol.base_asset.clone()).or_default().push(symbol.symbol.clone());
+            state.by_quote_asset.entry(symbol.quote_asset.clone()).or_default().push(symbol.symbol.clone());
+            state.symbols.insert(symbol.symbol.clone(), Arc::new(symbol));
+        }
+        state
+    }
+
+    fn collect(&self, names: Option<&Vec<String>>) -> Vec<Arc<Symbol>> {
+        names
+            .map(|names| names.iter().filter_map(|name| self.symbols.get(name).cloned()).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Spot symbols from `/api/v3/exchangeInfo`, downloaded once and indexed by name and asset.
+/// Clones share the same cache, so one registry can serve the order code and the websockets.
+///
+/// Lookups never hit the network: they answer from the last `refresh`, which
+/// `get_symbol` runs on first use and `start_refresh` repeats in the background.
+#[derive(Clone)]
+pub struct SymbolRegistry {
+    general: General,
+    state: Arc<RwLock<RegistryState>>,
+}
+
+impl SymbolRegistry {
+    pub fn new(general: General) -> Self {
+        SymbolRegistry { general, state: Arc::new(RwLock::new(RegistryState::default())) }
+    }
+
+    /// Download the exchange information again and replace the cache
+    pub async fn refresh(&self) -> Result<(), BinanceErr> {
+        let info = self.general.exchange_info().await?;
+        let state = RegistryState::from_exchange_info(info);
+        *self.state.write().unwrap_or_else(|e| e.into_inner()) = state;
+
+        Ok(())
+    }
+
+    /// Refresh every `interval` until the returned task is dropped.
+    /// On failure the cache is kept until the next round.
+    pub fn start_refresh(&self, interval: Duration) -> SymbolRefreshTask {
+        let registry = self.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                registry.refresh().await.ok();
+                tokio::time::sleep(interval).await;
+            }
+        });
+        SymbolRefreshTask { task }
+    }
+
+    /// When the cache was last filled, `None` until the first `refresh`
+    pub fn refreshed_at(&self) -> Option<Instant> {
+        self.read(|state| state.refreshed_at)
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.refreshed_at().is_some()
+    }
+
+    /// Cached symbol, loading the registry first if it never was
+    pub async fn get_symbol(&self, symbol: &str) -> Result<Arc<Symbol>, BinanceErr> {
+        if !self.is_loaded() {
+            self.refresh().await?;
+        }
+        self.symbol(symbol).ok_or_else(|| BinanceErr::UnknownSymbol(symbol.to_uppercase()))
+    }
+
+    /// Cached symbol, case insensitive
+    pub fn symbol(&self, symbol: &str) -> Option<Arc<Symbol>> {
+        self.read(|state| state.symbols.get(&symbol.to_uppercase()).cloned())
+    }
+
+    pub fn contains(&self, symbol: &str) -> bool {
+        self.symbol(symbol).is_some()
+    }
+
+    /// Symbols trading `asset` against something else, e.g. BTC -> BTCUSDT, BTCEUR, ...
+    pub fn symbols_with_base_asset(&self, asset: &str) -> Vec<Arc<Symbol>> {
+        self.read(|state| state.collect(state.by_base_asset.get(&asset.to_uppercase())))
+    }
+
+    /// Symbols quoted in `asset`, e.g. USDT -> BTCUSDT, ETHUSDT, ...
+    pub fn symbols_with_quote_asset(&self, asset: &str) -> Vec<Arc<Symbol>> {
+        self.read(|state| state.collect(state.by_quote_asset.get(&asset.to_uppercase())))
+    }
+
+    pub fn filters(&self, symbol: &str) -> Option<Vec<Filters>> {
+        self.symbol(symbol).map(|symbol| symbol.filters.clone())
+    }
+
+    /// PRICE_FILTER tick size
+    pub fn tick_size(&self, symbol: &str) -> Option<Number> {
+        self.symbol(symbol)?.filters.iter().find_map(|filter| match filter {
+            Filters::PriceFilter { tick_size, .. } => Some(*tick_size),
+            _ => None
+        })
+    }
+
+    /// LOT_SIZE step size
+    pub fn step_size(&self, symbol: &str) -> Option<Number> {
+        self.symbol(symbol)?.filters.iter().find_map(|filter| match filter {
+            Filters::LotSize { step_size, .. } => Some(*step_size),
+            _ => None
+        })
+    }
+
+    /// Decimals a price can have, from the tick size
+    pub fn price_precision(&self, symbol: &str) -> Option<u32> {
+        self.tick_size(symbol).map(decimals)
+    }
+
+    /// Decimals a quantity can have, from the step size
+    pub fn quantity_precision(&self, symbol: &str) -> Option<u32> {
+        self.step_size(symbol).map(decimals)
+    }
+
+    /// `NewOrder::apply_filters` with the filters of the order's symbol
+    pub async fn apply_filters(&self, order: NewOrder, average_price: Option<Number>) -> Result<NewOrder, BinanceErr> {
+        let symbol = self.get_symbol(&order.symbol).await?;
+        order.apply_filters(&symbol.filters, average_price)
+    }
+
+    fn read<T, F: FnOnce(&RegistryState) -> T>(&self, f: F) -> T {
+        f(&self.state.read().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// Decimals of a step like `0.00100000`, i.e. 3
+fn decimals(step: Number) -> u32 {
+    to_parameter(step).split_once('.').map_or(0, |(_, decimals)| decimals.len() as u32)
+}
+
+/// Background refresh started by `SymbolRegistry::start_refresh`, stopped when dropped
+#[derive(Debug)]
+pub struct SymbolRefreshTask {
+    task: JoinHandle<()>,
+}
+
+impl SymbolRefreshTask {
+    /// Same as dropping the task
+    pub fn stop(self) {}
+
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+impl Drop for SymbolRefreshTask {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::api::Binance;
+    use crate::general::General;
+    use crate::mock_server::MockBinanceServer;
+    use crate::symbols::*;
+
+    #[tokio::test]
+    async fn indexes_the_exchange_information_once() {
+        let server = MockBinanceServer::start().await.unwrap();
+        let registry = SymbolRegistry::new(General::new_with_config(None, None, &server.config()));
+        assert!(registry.symbol("BTCUSDT").is_none());
+
+        assert_eq!(registry.get_symbol("btcusdt").await.unwrap().quote_asset, "USDT");
+        assert!(registry.get_symbol("ETHBTC").await.is_ok());
+        assert!(matches!(registry.get_symbol("NOPE").await, Err(BinanceErr::UnknownSymbol(_))));
+        let downloads = server.requests().iter().filter(|r| r.path().ends_with("/exchangeInfo")).count();
+        assert_eq!(downloads, 1);
+
+        let quoted_in_btc: Vec<_> = registry.symbols_with_quote_asset("BTC").iter().map(|s| s.symbol.clone()).collect();
+        assert_eq!(quoted_in_btc, vec!["ETHBTC"]);
+        assert_eq!(registry.price_precision("BTCUSDT"), Some(2));
+        assert_eq!(registry.quantity_precision("BTCUSDT"), Some(5));
+    }
+
+    #[tokio::test]
+    async fn refreshes_until_the_task_is_dropped() {
+        let server = MockBinanceServer::start().await.unwrap();
+        let registry = SymbolRegistry::new(General::new_with_config(None, None, &server.config()));
+        let downloads = || server.requests().iter().filter(|r| r.path().ends_with("/exchangeInfo")).count();
+
+        let task = registry.start_refresh(Duration::from_millis(20));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(registry.is_loaded());
+        assert!(downloads() >= 2);
+
+        drop(task);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let stopped_at = downloads();
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(downloads(), stopped_at);
+    }
+}
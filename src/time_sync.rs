@@ -0,0 +1,172 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+
+use crate::client::{parse_response, Client};
+use crate::error::BinanceErr;
+use crate::model::ServerTime;
+use crate::util::get_timestamp;
+
+#[derive(Debug, Default)]
+struct TimeSyncState {
+    offset: AtomicI64,
+    latency: AtomicU64,
+    synced: AtomicBool,
+}
+
+/// Offset between the local clock and the Binance server clock, applied to the
+/// `timestamp` of every signed request. Shared by every clone of a `Client`.
+///
+/// Stays at a zero offset (plain local time) until `General::sync_time` or
+/// `General::start_time_sync` has measured it. A request rejected for its timestamp
+/// (-1021) measures it again before being resent.
+#[derive(Debug, Clone, Default)]
+pub struct TimeSync {
+    state: Arc<TimeSyncState>,
+}
+
+impl TimeSync {
+    pub fn new() -> Self {
+        TimeSync::default()
+    }
+
+    /// Current time in milliseconds, corrected to the server clock
+    pub fn timestamp(&self) -> Result<u64, BinanceErr> {
+        let local = get_timestamp()? as i64;
+        Ok((local + self.state.offset.load(Ordering::Relaxed)).max(0) as u64)
+    }
+
+    /// Server time minus local time, in milliseconds
+    pub fn offset(&self) -> Option<i64> {
+        if self.is_synced() {
+            Some(self.state.offset.load(Ordering::Relaxed))
+        } else {
+            None
+        }
+    }
+
+    /// Round trip of the last `/time` request
+    pub fn latency(&self) -> Option<Duration> {
+        if self.is_synced() {
+            Some(Duration::from_millis(self.state.latency.load(Ordering::Relaxed)))
+        } else {
+            None
+        }
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.state.synced.load(Ordering::Relaxed)
+    }
+
+    /// Record a server time read between `sent` and `received` (local milliseconds).
+    /// The server is assumed to have stamped it half way through the round trip.
+    pub fn record(&self, sent: u64, server_time: u64, received: u64) {
+        let latency = received.saturating_sub(sent);
+        let midpoint = sent + latency / 2;
+        self.state.offset.store(server_time as i64 - midpoint as i64, Ordering::Relaxed);
+        self.state.latency.store(latency, Ordering::Relaxed);
+        self.state.synced.store(true, Ordering::Relaxed);
+    }
+
+    /// Measure the offset with the server time read from `endpoint` (`/api/v3/time` or `/fapi/v1/time`)
+    pub async fn sync(&self, client: &Client, endpoint: &str) -> Result<(), BinanceErr> {
+        let sent = get_timestamp()?;
+        let data = client.get_once(endpoint).await?;
+        let received = get_timestamp()?;
+        let server_time: ServerTime = parse_response(endpoint, &data)?;
+        self.record(sent, server_time.server_time, received);
+
+        Ok(())
+    }
+
+    /// Measure the offset every `interval` until the returned task is dropped.
+    /// On failure the last known offset is kept until the next round.
+    pub fn start(&self, client: Client, endpoint: &'static str, interval: Duration) -> TimeSyncTask {
+        let time_sync = self.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                time_sync.sync(&client, endpoint).await.ok();
+                tokio::time::sleep(interval).await;
+            }
+        });
+        TimeSyncTask { task }
+    }
+}
+
+/// Background sync started by `TimeSync::start`, stopped when dropped
+#[derive(Debug)]
+pub struct TimeSyncTask {
+    task: JoinHandle<()>,
+}
+
+impl TimeSyncTask {
+    /// Same as dropping the task
+    pub fn stop(self) {}
+
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+impl Drop for TimeSyncTask {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// The time endpoint of the same API as `endpoint`
+pub(crate) fn time_endpoint(endpoint: &str) -> &'static str {
+    if endpoint.starts_with("/fapi/") {
+        "/fapi/v1/time"
+    } else {
+        "/api/v3/time"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use reqwest::Method;
+
+    use crate::api::Binance;
+    use crate::general::General;
+    use crate::mock_server::MockBinanceServer;
+    use crate::time_sync::*;
+
+    #[test]
+    fn the_server_stamps_its_time_half_way_through_the_round_trip() {
+        let time_sync = TimeSync::new();
+        assert_eq!((time_sync.offset(), time_sync.latency()), (None, None));
+
+        time_sync.record(1_000, 5_000, 1_100);
+        assert_eq!(time_sync.offset(), Some(3_950));
+        assert_eq!(time_sync.latency(), Some(Duration::from_millis(100)));
+        let (local, corrected) = (get_timestamp().unwrap(), time_sync.timestamp().unwrap());
+        assert!(corrected >= local + 3_950 && corrected < local + 4_950);
+
+        // A server clock behind the local one
+        time_sync.record(10_000, 4_000, 10_000);
+        assert_eq!(time_sync.offset(), Some(-6_000));
+    }
+
+    #[tokio::test]
+    async fn syncs_until_the_task_is_dropped() {
+        let server = MockBinanceServer::start().await.unwrap();
+        let general: General = Binance::new_with_config(None, None, &server.config());
+        let time_requests = || server.requests().iter().filter(|r| r.method == Method::GET && r.path() == "/api/v3/time").count();
+
+        let task = general.start_time_sync(Duration::from_millis(20));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(general.client.time_sync().is_synced());
+        assert!(time_requests() >= 2);
+
+        task.stop();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let stopped_at = time_requests();
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(time_requests(), stopped_at);
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use reqwest::{Method, StatusCode};
+use reqwest::header::HeaderMap;
+
+use crate::config::Config;
+use crate::error::BinanceErr;
+
+/// A fully built HTTP request, signature included
+#[derive(Debug, Clone)]
+pub struct TransportRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: Option<String>,
+}
+
+impl TransportRequest {
+    /// Path of the url, e.g. `/api/v3/order`
+    pub fn path(&self) -> &str {
+        let without_query = self.url.split('?').next().unwrap_or("");
+        match without_query.find("://") {
+            Some(scheme_end) => {
+                let after_scheme = &without_query[scheme_end + 3..];
+                after_scheme.find('/').map_or("", |path_start| &after_scheme[path_start..])
+            }
+            None => without_query
+        }
+    }
+
+    /// Query string of the url, without the leading `?`
+    pub fn query(&self) -> &str {
+        self.url.split_once('?').map_or("", |(_, query)| query)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TransportResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+/// Sends the requests built by `Client`. The default goes over the network with reqwest,
+/// `MockTransport` answers from memory.
+#[async_trait::async_trait]
+pub trait Transport: Send + Sync {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, BinanceErr>;
+}
+
+/// HTTP settings of a `Config`, clients built from equal ones share a connection pool
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PoolSettings {
+    timeout: Duration,
+    connect_timeout: Duration,
+    pool_idle_timeout: Option<Duration>,
+    tcp_keepalive: Option<Duration>,
+    user_agent: String,
+}
+
+static SHARED_POOLS: OnceLock<Mutex<HashMap<PoolSettings, Arc<ReqwestTransport>>>> = OnceLock::new();
+
+/// Pooled reqwest client configured from `Config`
+pub struct ReqwestTransport {
+    inner: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    /// A connection pool of its own, failing on an invalid user agent, proxy or TLS setup
+    pub fn new(config: &Config) -> Result<Self, BinanceErr> {
+        let mut builder = reqwest::Client::builder()
+            .user_agent(config.user_agent.as_str())
+            .connect_timeout(config.connect_timeout)
+            .timeout(config.timeout)
+            .pool_idle_timeout(config.pool_idle_timeout)
+            .tcp_keepalive(config.tcp_keepalive)
+            .tcp_nodelay(true);
+        if let Some(proxy) = &config.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        Ok(ReqwestTransport { inner: builder.build()? })
+    }
+
+    /// The pool of every client built from the same HTTP settings, so that handles created
+    /// with `Binance::new` reuse connections too. A proxy always gets a pool of its own.
+    pub fn shared(config: &Config) -> Result<Arc<Self>, BinanceErr> {
+        if config.proxy.is_some() {
+            return ReqwestTransport::new(config).map(Arc::new);
+        }
+        let settings = PoolSettings {
+            timeout: config.timeout,
+            connect_timeout: config.connect_timeout,
+            pool_idle_timeout: config.pool_idle_timeout,
+            tcp_keepalive: config.tcp_keepalive,
+            user_agent: config.user_agent.clone(),
+        };
+        let mut pools = SHARED_POOLS.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(transport) = pools.get(&settings) {
+            return Ok(transport.clone());
+        }
+        let transport = Arc::new(ReqwestTransport::new(config)?);
+        pools.insert(settings, transport.clone());
+        Ok(transport)
+    }
+}
+
+/// Stands in for a `ReqwestTransport` that could not be built, every request fails with the reason
+pub(crate) struct UnavailableTransport {
+    pub(crate) reason: String,
+}
+
+#[async_trait::async_trait]
+impl Transport for UnavailableTransport {
+    async fn send(&self, _: TransportRequest) -> Result<TransportResponse, BinanceErr> {
+        Err(BinanceErr::from_str(format!("HTTP client unavailable: {}", self.reason)))
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for ReqwestTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, BinanceErr> {
+        let mut builder = self.inner.request(request.method, request.url.as_str()).headers(request.headers);
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+        let response = builder.send().await?;
+
+        Ok(TransportResponse {
+            status: response.status(),
+            headers: response.headers().clone(),
+            body: response.text().await?,
+        })
+    }
+}
+
+/// In-memory transport for tests: answers with scripted responses per method and path,
+/// and records every request it receives.
+///
+/// Responses queued with `once` are used first, in order, then the one set with `on` is
+/// repeated. Anything else gets a 404.
+#[derive(Default)]
+pub struct MockTransport {
+    responses: Mutex<HashMap<(Method, String), MockRoute>>,
+    requests: Mutex<Vec<TransportRequest>>,
+}
+
+#[derive(Default)]
+struct MockRoute {
+    queued: VecDeque<TransportResponse>,
+    repeated: Option<TransportResponse>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        MockTransport::default()
+    }
+
+    /// Answer every `method` request on `path` with this status and body
+    pub fn on<S: Into<String>>(&self, method: Method, path: &str, status: u16, body: S) -> &Self {
+        let mut responses = self.responses.lock().unwrap_or_else(|e| e.into_inner());
+        responses.entry((method, path.to_string())).or_default().repeated = Some(mock_response(status, body.into()));
+        self
+    }
+
+    /// Answer the next `method` request on `path` with this status and body
+    pub fn once<S: Into<String>>(&self, method: Method, path: &str, status: u16, body: S) -> &Self {
+        let mut responses = self.responses.lock().unwrap_or_else(|e| e.into_inner());
+        responses.entry((method, path.to_string())).or_default().queued.push_back(mock_response(status, body.into()));
+        self
+    }
+
+    /// Requests received so far, oldest first
+    pub fn requests(&self) -> Vec<TransportRequest> {
+        self.requests.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Record `request` and pop its scripted response, if any
+    pub(crate) fn respond(&self, request: TransportRequest) -> Option<TransportResponse> {
+        let key = (request.method.clone(), request.path().to_string());
+        self.requests.lock().unwrap_or_else(|e| e.into_inner()).push(request);
+
+        let mut responses = self.responses.lock().unwrap_or_else(|e| e.into_inner());
+        responses
+            .get_mut(&key)
+            .and_then(|route| route.queued.pop_front().or_else(|| route.repeated.clone()))
+    }
+}
+
+pub(crate) fn mock_response(status: u16, body: String) -> TransportResponse {
+    TransportResponse {
+        status: StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+        headers: HeaderMap::new(),
+        body,
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for MockTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, BinanceErr> {
+        let route = format!("{} {}", request.method, request.path());
+        Ok(self.respond(request).unwrap_or_else(|| {
+            let msg = format!("No mock response for {}", route);
+            mock_response(404, serde_json::json!({ "code": -1000, "msg": msg }).to_string())
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use reqwest::Method;
+
+    use crate::account::Account;
+    use crate::api::Binance;
+    use crate::client::Client;
+    use crate::config::Config;
+    use crate::error::BinanceErrorCode;
+    use crate::numeric::Number;
+    use crate::retry::RetryPolicy;
+    use crate::transport::*;
+
+    fn mock_account(mock: &Arc<MockTransport>) -> Account {
+        let config = Config::default().set_retry_policy(RetryPolicy::none());
+        let client = Client::new_with_config(Some("key".into()), Some("secret".into()), "http://mock".into(), &config)
+            .with_transport(mock.clone());
+        Account::new_with_client(&client, &config)
+    }
+
+    #[tokio::test]
+    async fn orders_are_signed_and_answered_by_the_mock() {
+        let mock = Arc::new(MockTransport::new());
+        mock.on(Method::POST, "/api/v3/order", 200, r#"{"symbol":"BTCUSDT","orderId":28,"clientOrderId":"6gCrw2kRUAF9CvJDGP16IP","transactTime":1507725176595,"price":"0.1","origQty":"1.0","executedQty":"0.0","cummulativeQuoteQty":"0.0","status":"NEW","timeInForce":"GTC","side":"BUY","fills":[]}"#);
+
+        let transaction = mock_account(&mock).limit_buy("BTCUSDT", 1, "0.1".parse::<Number>().unwrap()).await.unwrap();
+        assert_eq!(transaction.order_id, 28);
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path(), "/api/v3/order");
+        assert!(requests[0].query().starts_with("price=0.1&quantity=1&recvWindow=5000&side=BUY&symbol=BTCUSDT"));
+        assert!(requests[0].query().contains("&signature="));
+        assert_eq!(requests[0].headers["x-mbx-apikey"], "key");
+    }
+
+    #[tokio::test]
+    async fn equal_http_settings_share_a_pool() {
+        let config = Config::default();
+        assert!(Arc::ptr_eq(&ReqwestTransport::shared(&config).unwrap(), &ReqwestTransport::shared(&config).unwrap()));
+        let other = Config::default().set_timeout(std::time::Duration::from_secs(1));
+        assert!(!Arc::ptr_eq(&ReqwestTransport::shared(&config).unwrap(), &ReqwestTransport::shared(&other).unwrap()));
+
+        // An invalid user agent is reported by the requests instead of panicking
+        let invalid = Config::default().set_user_agent("bad\nagent");
+        assert!(ReqwestTransport::new(&invalid).is_err());
+        let general: crate::general::General = Binance::new_with_config(None, None, &invalid);
+        assert!(general.ping().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn scripted_errors_surface_as_binance_errors() {
+        let mock = Arc::new(MockTransport::new());
+        mock.once(Method::GET, "/api/v3/account", 400, r#"{"code":-1021,"msg":"Timestamp for this request is outside of the recvWindow."}"#);
+
+        let error = mock_account(&mock).get_account().await.unwrap_err();
+        assert_eq!(error.error_code(), Some(BinanceErrorCode::InvalidTimestamp));
+        assert_eq!(error.endpoint(), Some("/api/v3/account"));
+    }
+}
@@ -1,4 +1,9 @@
-use serde_json::from_str;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
 use uuid::Uuid;
 
 use crate::client::*;
@@ -8,57 +13,253 @@ use crate::websocket::*;
 
 static USER_DATA_STREAM: &str = "/api/v3/userDataStream";
 
+/// Listen keys expire 60 minutes after they were last kept alive
+pub const LISTEN_KEY_KEEPALIVE: Duration = Duration::from_secs(30 * 60);
+
+/// What `UserStream` did to keep the listen key of a subscription valid, see `WebsocketEvent::ListenKey`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum ListenKeyEvent {
+    KeptAlive { listen_key: String },
+    /// The key is replaced by a new one right away, it may be gone already
+    KeepAliveFailed { listen_key: String, reason: String },
+    /// The subscription moved to a new listen key after the previous one expired. Events sent in
+    /// between are missed, account state built from them should be resynced.
+    Renewed { listen_key: String },
+    /// Retried on every keepalive until a new key is obtained
+    RenewalFailed { reason: String },
+    /// Closed on `unsubscribe` or when the `UserStream` is dropped
+    Closed { listen_key: String },
+}
 
+type CloseAck = oneshot::Sender<Result<bool, BinanceErr>>;
+
+/// The user data stream. Every subscription has a listen key of its own, kept alive in the
+/// background, replaced when it expires and closed on `unsubscribe` or when this is dropped.
 pub struct UserStream {
     pub client: Client,
     pub recv_window: u64,
-    pub ws: Option<Websocket>,
+    pub ws_endpoint: String,
+    pub(crate) keepalive_interval: Duration,
+    /// Asks the listen key manager of each subscription to close it
+    pub(crate) listen_keys: HashMap<Uuid, oneshot::Sender<CloseAck>>,
 }
 
 #[async_trait::async_trait]
 pub trait UserStreamAsync {
-    async fn subscribe(&mut self) -> Result<Uuid, BinanceErr>;
-    fn unsubscribe(&mut self, uuid: Uuid) -> Option<StoredStream>;
+    /// The user data events, `SubscriptionStream::id` identifies the subscription
+    async fn subscribe(&mut self) -> Result<SubscriptionStream, BinanceErr>;
+    /// End the subscription and close its listen key, `false` if it is unknown or already ended
+    async fn unsubscribe(&mut self, uuid: Uuid) -> Result<bool, BinanceErr>;
 }
 
 #[async_trait::async_trait]
 impl UserStreamAsync for UserStream {
-    async fn subscribe(&mut self) -> Result<Uuid, BinanceErr> {
-        match self.start().await? {
-            UserDataStream { listen_key } => {
-                let mut ws = Websocket::new();
-                let id = ws.subscribe(WebsocketStreamType::UserStream(listen_key)).await?;
-                self.ws = Some(ws);
-                Ok(id)
+    async fn subscribe(&mut self) -> Result<SubscriptionStream, BinanceErr> {
+        let UserDataStream { listen_key } = self.start().await?;
+        let mut websocket = Websocket::new_with_endpoint(self.ws_endpoint.clone());
+        let events = match websocket.subscribe(WebsocketStreamType::UserStream(listen_key.clone())).await {
+            Ok(events) => events,
+            Err(e) => {
+                self.close(&listen_key).await.ok();
+                return Err(e);
             }
-        }
+        };
+        let (close, close_requests) = oneshot::channel();
+        let manager = ListenKeyManager {
+            client: self.client.clone(),
+            websocket,
+            subscription: events.id(),
+            listen_key,
+        };
+        tokio::spawn(manager.run(self.keepalive_interval, events.fan_out(), close_requests));
+        self.listen_keys.insert(events.id(), close);
+        Ok(events)
     }
 
-    fn unsubscribe(&mut self, uuid: Uuid) -> Option<StoredStream> {
-        if let Some(ws) = &mut self.ws {
-            ws.unsubscribe(uuid)
-        } else {
-            None
+    async fn unsubscribe(&mut self, uuid: Uuid) -> Result<bool, BinanceErr> {
+        let close = match self.listen_keys.remove(&uuid) {
+            Some(close) => close,
+            None => return Ok(false)
+        };
+        let (ack, closed) = oneshot::channel();
+        if close.send(ack).is_err() {
+            // The manager is gone with the subscription, reconnecting was given up
+            return Ok(false);
         }
+        closed.await.unwrap_or(Ok(false))
     }
 }
 
 impl UserStream {
+    /// How often listen keys are kept alive, `LISTEN_KEY_KEEPALIVE` by default
+    pub fn set_keepalive_interval(mut self, keepalive_interval: Duration) -> Self {
+        self.keepalive_interval = keepalive_interval;
+        self
+    }
+
     pub async fn start(&self) -> Result<UserDataStream, BinanceErr> {
-        let data = self.client.post(USER_DATA_STREAM).await?;
-        let user_data_stream: UserDataStream = from_str(data.as_str())?;
-        Ok(user_data_stream)
+        start(&self.client).await
     }
 
     pub async fn keep_alive(&self, listen_key: &str) -> Result<Success, BinanceErr> {
-        let data = self.client.put(USER_DATA_STREAM, listen_key).await?;
-        let success: Success = from_str(data.as_str())?;
-        Ok(success)
+        keep_alive(&self.client, listen_key).await
     }
 
     pub async fn close(&self, listen_key: &str) -> Result<Success, BinanceErr> {
-        let data = self.client.delete(USER_DATA_STREAM, listen_key).await?;
-        let success: Success = from_str(data.as_str())?;
-        Ok(success)
+        close(&self.client, listen_key).await
+    }
+}
+
+async fn start(client: &Client) -> Result<UserDataStream, BinanceErr> {
+    let data = client.post(USER_DATA_STREAM).await?;
+    let user_data_stream: UserDataStream = parse_response(USER_DATA_STREAM, &data)?;
+    Ok(user_data_stream)
+}
+
+async fn keep_alive(client: &Client, listen_key: &str) -> Result<Success, BinanceErr> {
+    let data = client.put(USER_DATA_STREAM, listen_key).await?;
+    let success: Success = parse_response(USER_DATA_STREAM, &data)?;
+    Ok(success)
+}
+
+async fn close(client: &Client, listen_key: &str) -> Result<Success, BinanceErr> {
+    let data = client.delete(USER_DATA_STREAM, listen_key).await?;
+    let success: Success = parse_response(USER_DATA_STREAM, &data)?;
+    Ok(success)
+}
+
+/// Owns the websocket of one user data subscription and the listen key it is addressed by
+struct ListenKeyManager {
+    client: Client,
+    websocket: Websocket,
+    subscription: Uuid,
+    listen_key: String,
+}
+
+impl ListenKeyManager {
+    async fn run(mut self, interval: Duration, mut events: SubscriptionStream, mut close_requests: oneshot::Receiver<CloseAck>) {
+        let mut keepalive = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+        // Set while the key could not be replaced, retried on every keepalive
+        let mut expired = false;
+        loop {
+            tokio::select! {
+                _ = keepalive.tick() => {
+                    if expired {
+                        expired = !self.renew().await;
+                        continue;
+                    }
+                    match keep_alive(&self.client, &self.listen_key).await {
+                        Ok(_) => self.notify(ListenKeyEvent::KeptAlive { listen_key: self.listen_key.clone() }),
+                        Err(e) => {
+                            self.notify(ListenKeyEvent::KeepAliveFailed { listen_key: self.listen_key.clone(), reason: e.to_string() });
+                            expired = !self.renew().await;
+                        }
+                    }
+                },
+                event = events.next() => match event {
+                    Some(Ok(SubscriptionEvent { event: WebsocketEvent::ListenKeyExpired(event), .. })) if event.listen_key == self.listen_key => {
+                        expired = !self.renew().await;
+                    }
+                    Some(_) => {}
+                    // Reconnecting was given up, the subscription is over
+                    None => {
+                        close(&self.client, &self.listen_key).await.ok();
+                        return;
+                    }
+                },
+                // Asked by `unsubscribe`, or the `UserStream` was dropped
+                ack = &mut close_requests => {
+                    let closed = close(&self.client, &self.listen_key).await;
+                    self.notify(ListenKeyEvent::Closed { listen_key: self.listen_key.clone() });
+                    let unsubscribed = self.websocket.unsubscribe(self.subscription).await;
+                    if let Ok(ack) = ack {
+                        ack.send(closed.and(unsubscribed)).ok();
+                    }
+                    return;
+                },
+            }
+        }
+    }
+
+    /// Move the subscription to a new listen key, `false` if none could be obtained
+    async fn renew(&mut self) -> bool {
+        let listen_key = match start(&self.client).await {
+            Ok(UserDataStream { listen_key }) => listen_key,
+            Err(e) => {
+                self.notify(ListenKeyEvent::RenewalFailed { reason: e.to_string() });
+                return false;
+            }
+        };
+        if let Err(e) = self.websocket.replace_listen_key(self.subscription, listen_key.clone()).await {
+            close(&self.client, &listen_key).await.ok();
+            self.notify(ListenKeyEvent::RenewalFailed { reason: e.to_string() });
+            return false;
+        }
+        let previous = std::mem::replace(&mut self.listen_key, listen_key);
+        // Most likely expired already
+        close(&self.client, &previous).await.ok();
+        self.notify(ListenKeyEvent::Renewed { listen_key: self.listen_key.clone() });
+        true
+    }
+
+    fn notify(&self, event: ListenKeyEvent) {
+        self.websocket.notify(self.subscription, event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+    use reqwest::Method;
+    use serde_json::json;
+
+    use crate::api::Binance;
+    use crate::mock_server::MockBinanceServer;
+    use crate::userstream::*;
+
+    /// The next event other than a keepalive
+    async fn next_event(events: &mut SubscriptionStream) -> Option<WebsocketEvent> {
+        while let Some(event) = events.try_next().await.unwrap() {
+            match event.event {
+                WebsocketEvent::ListenKey(ListenKeyEvent::KeptAlive { .. }) => continue,
+                event => return Some(event),
+            }
+        }
+        None
+    }
+
+    #[tokio::test]
+    async fn keeps_renews_and_closes_the_listen_key() {
+        let server = MockBinanceServer::start().await.unwrap();
+        let mut user_stream = UserStream::new_with_config(Some("key".into()), None, &server.config())
+            .set_keepalive_interval(Duration::from_millis(100));
+        let mut events = user_stream.subscribe().await.unwrap();
+        let first_key = server.subscriptions().into_iter().next().unwrap();
+
+        match events.try_next().await.unwrap().map(|e| e.event) {
+            Some(WebsocketEvent::ListenKey(ListenKeyEvent::KeptAlive { listen_key })) => assert_eq!(listen_key, first_key),
+            other => panic!("unexpected event {:?}", other)
+        }
+        assert!(server.requests().iter().any(|r| r.method == Method::PUT && r.path() == "/api/v3/userDataStream"));
+
+        server.push_event(&first_key, json!({ "e": "listenKeyExpired", "E": 1576653824250u64, "listenKey": first_key }));
+        assert!(matches!(next_event(&mut events).await, Some(WebsocketEvent::ListenKeyExpired(_))));
+        let second_key = match next_event(&mut events).await {
+            Some(WebsocketEvent::ListenKey(ListenKeyEvent::Renewed { listen_key })) => listen_key,
+            other => panic!("unexpected event {:?}", other)
+        };
+        assert_ne!(second_key, first_key);
+        server.wait_for_subscription(&second_key).await;
+        server.push_event(&second_key, json!({
+            "e": "balanceUpdate", "E": 1573200697110u64, "a": "BTC", "d": "100.00000000", "T": 1573200697068u64
+        }));
+        assert!(matches!(next_event(&mut events).await, Some(WebsocketEvent::BalanceUpdate(_))));
+
+        assert!(user_stream.unsubscribe(events.id()).await.unwrap());
+        assert!(matches!(next_event(&mut events).await,
+            Some(WebsocketEvent::ListenKey(ListenKeyEvent::Closed { listen_key })) if listen_key == second_key));
+        assert!(next_event(&mut events).await.is_none());
+        let closed = server.requests().iter().filter(|r| r.method == Method::DELETE && r.path() == "/api/v3/userDataStream").count();
+        assert_eq!(closed, 2);
     }
 }
@@ -4,6 +4,8 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use serde_json::Value;
 
 use crate::error::*;
+use crate::model::KlineSummary;
+use crate::numeric::Number;
 
 pub fn build_request(parameters: &BTreeMap<String, String>) -> String {
     let mut request = String::new();
@@ -17,39 +19,99 @@ pub fn build_request(parameters: &BTreeMap<String, String>) -> String {
 }
 
 pub fn build_signed_request(
-    mut parameters: BTreeMap<String, String>, recv_window: u64,
+    parameters: BTreeMap<String, String>, recv_window: u64,
 ) -> Result<String, BinanceErr> {
+    if let Ok(timestamp) = get_timestamp() {
+        Ok(build_signed_request_at(parameters, recv_window, timestamp))
+    } else {
+        Err(BinanceErr::from_str("Failed to get timestamp".into()))
+    }
+}
+
+/// Same as `build_signed_request`, with the timestamp supplied by the caller (e.g. a `TimeSync`)
+pub fn build_signed_request_at(
+    mut parameters: BTreeMap<String, String>, recv_window: u64, timestamp: u64,
+) -> String {
     if recv_window > 0 {
         parameters.insert("recvWindow".into(), recv_window.to_string());
     }
+    parameters.insert("timestamp".into(), timestamp.to_string());
 
-    if let Ok(timestamp) = get_timestamp() {
-        parameters.insert("timestamp".into(), timestamp.to_string());
+    build_request(&parameters)
+}
 
-        let mut request = String::new();
-        for (key, value) in &parameters {
-            let param = format!("{}={}&", key, value);
-            request.push_str(param.as_ref());
-        }
-        request.pop(); // remove last &
+pub fn to_i64(v: &Value) -> Result<i64, BinanceErr> {
+    v.as_i64().ok_or_else(|| malformed("expected an integer", v))
+}
 
-        Ok(request)
-    } else {
-        Err(BinanceErr::from_str(format!("Failed to get timestamp")))
-    }
+pub fn to_f64(v: &Value) -> Result<f64, BinanceErr> {
+    v.as_str().and_then(|s| s.parse().ok()).ok_or_else(|| malformed("expected a decimal string", v))
 }
 
-pub fn to_i64(v: &Value) -> i64 {
-    v.as_i64().unwrap()
+pub fn to_number(v: &Value) -> Result<Number, BinanceErr> {
+    v.as_str().and_then(|s| s.parse().ok()).ok_or_else(|| malformed("expected a decimal string", v))
 }
 
-pub fn to_f64(v: &Value) -> f64 {
-    v.as_str().unwrap().parse().unwrap()
+/// One row of the klines endpoints, `[open_time, "open", "high", .., "taker_buy_quote_asset_volume", ..]`
+pub fn to_kline_summary(row: &[Value]) -> Result<KlineSummary, BinanceErr> {
+    let field = |i: usize| row.get(i).unwrap_or(&Value::Null);
+    let malformed_row = |_: BinanceErr| malformed("unexpected kline", &Value::from(row.to_vec()));
+    Ok(KlineSummary {
+        open_time: to_i64(field(0)).map_err(malformed_row)?,
+        open: to_number(field(1)).map_err(malformed_row)?,
+        high: to_number(field(2)).map_err(malformed_row)?,
+        low: to_number(field(3)).map_err(malformed_row)?,
+        close: to_number(field(4)).map_err(malformed_row)?,
+        volume: to_number(field(5)).map_err(malformed_row)?,
+        close_time: to_i64(field(6)).map_err(malformed_row)?,
+        quote_asset_volume: to_number(field(7)).map_err(malformed_row)?,
+        number_of_trades: to_i64(field(8)).map_err(malformed_row)?,
+        taker_buy_base_asset_volume: to_number(field(9)).map_err(malformed_row)?,
+        taker_buy_quote_asset_volume: to_number(field(10)).map_err(malformed_row)?,
+    })
 }
 
-fn get_timestamp() -> Result<u64, BinanceErr> {
+fn malformed(msg: &str, payload: &Value) -> BinanceErr {
+    BinanceErr::MalformedPayload { msg: msg.into(), payload: payload.to_string(), endpoint: None }
+}
+
+/// Swap the `timestamp` of an already built signed request for the current time, used when resending it
+pub(crate) fn refresh_request_timestamp(request: &str, timestamp: u64) -> String {
+    request
+        .split('&')
+        .map(|pair| {
+            if pair.starts_with("timestamp=") {
+                format!("timestamp={}", timestamp)
+            } else {
+                pair.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+pub(crate) fn get_timestamp() -> Result<u64, BinanceErr> {
     let start = SystemTime::now();
     let since_epoch = start.duration_since(UNIX_EPOCH)?;
 
     Ok(since_epoch.as_secs() * 1000 + u64::from(since_epoch.subsec_nanos()) / 1_000_000)
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::util::*;
+
+    #[test]
+    fn malformed_klines_are_errors() {
+        let row = json!([1499040000000i64, "0.01634790", "0.80000000", "0.01575800", "0.01577100", "148976.11427815",
+            1499644799999i64, "2434.19055334", 308, "1756.87402397", "28.46694368", "0"]);
+        assert_eq!(to_kline_summary(row.as_array().unwrap()).unwrap().number_of_trades, 308);
+
+        let short_row = json!([1499040000000i64, "0.01634790"]);
+        assert!(matches!(to_kline_summary(short_row.as_array().unwrap()),
+            Err(BinanceErr::MalformedPayload { payload, .. }) if payload == r#"[1499040000000,"0.01634790"]"#));
+        assert!(to_number(&json!(1.5)).is_err());
+    }
+}
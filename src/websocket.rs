@@ -1,33 +1,38 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::pin::Pin;
 use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
-use futures::{prelude::*, SinkExt, stream::SplitStream, StreamExt};
-use pin_project::*;
+use futures::prelude::*;
 use serde::*;
-use streamunordered::{StreamUnordered, StreamYield};
-use tokio::net::TcpStream;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
-use tokio_tungstenite::WebSocketStream;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
 use url::Url;
 use uuid::Uuid;
 
+use crate::config::Config;
+use crate::connection::{Ack, Command, Connection, ConnectionEvent, LastMessages, Subscribers};
 use crate::error::*;
 use crate::model::*;
+use crate::retry::RetryPolicy;
+use crate::symbols::SymbolRegistry;
+use crate::userstream::ListenKeyEvent;
 
-type WSStream = WebSocketStream<tokio_tungstenite::stream::Stream<TcpStream, tokio_native_tls::TlsStream<TcpStream>>>;
-pub type StoredStream = SplitStream<WSStream>;
-
-const METHOD: &'static str = "SUBSCRIBE";
+const METHOD: &str = "SUBSCRIBE";
 
+/// A SUBSCRIBE / UNSUBSCRIBE / LIST_SUBSCRIPTIONS frame
 #[derive(Serialize, Deserialize)]
 pub struct ExchangeSettings {
     method: &'static str,
     params: Vec<String>,
-    id: i64,
+    id: u64,
 }
 
 impl ExchangeSettings {
+    pub fn new(method: &'static str, params: Vec<String>, id: u64) -> Self {
+        ExchangeSettings { method, params, id }
+    }
     pub fn make_trade_param(endpoint: String) -> String {
         format!("{}@trade", endpoint.to_lowercase())
     }
@@ -49,15 +54,33 @@ impl ExchangeSettings {
                 let interval = interval.format_interval();
                 s.into_iter().map(|e| format!("{}@kline_{}", e.to_lowercase(), interval)).collect()
             }
-            WebsocketStreamType::PartialBookDepthStream(s) => {
-                s.into_iter().map(|e| format!("{}@lastUpdateId", e.to_lowercase())).collect()
+            WebsocketStreamType::PartialBookDepthStream { symbols: s, levels, speed } => {
+                s.into_iter()
+                    .map(|e| format!("{}@depth{}{}", e.to_lowercase(), levels.as_u8(), speed.stream_suffix()))
+                    .collect()
             }
-            WebsocketStreamType::DiffDepthStream(s) => {
-                s.into_iter().map(|e| format!("{}@depthUpdate", e.to_lowercase())).collect()
+            WebsocketStreamType::DiffDepthStream { symbols: s, speed } => {
+                s.into_iter().map(|e| format!("{}@depth{}", e.to_lowercase(), speed.stream_suffix())).collect()
             }
             WebsocketStreamType::BookTicker(s) => {
                 s.into_iter().map(|e| format!("{}@bookTicker", e.to_lowercase())).collect()
             }
+            WebsocketStreamType::BookTickerAll => vec![BOOK_TICKER_ALL_STREAM.into()],
+            WebsocketStreamType::MiniTicker(s) => {
+                s.into_iter().map(|e| format!("{}@miniTicker", e.to_lowercase())).collect()
+            }
+            WebsocketStreamType::MiniTickerAll => vec!["!miniTicker@arr".into()],
+            WebsocketStreamType::RollingWindowTicker { symbols: s, window } => {
+                s.into_iter().map(|e| format!("{}@ticker_{}", e.to_lowercase(), window.as_str())).collect()
+            }
+            WebsocketStreamType::RollingWindowTickerAll(window) => vec![format!("!ticker_{}@arr", window.as_str())],
+            WebsocketStreamType::AveragePrice(s) => {
+                s.into_iter().map(|e| format!("{}@avgPrice", e.to_lowercase())).collect()
+            }
+            WebsocketStreamType::KlineWithTimezone { symbols: s, mut interval, timezone } => {
+                let interval = interval.format_interval();
+                s.into_iter().map(|e| format!("{}@kline_{}@{}", e.to_lowercase(), interval, timezone)).collect()
+            }
             _ => vec![]
         }
     }
@@ -96,15 +119,95 @@ impl KlineInterval {
     }
 }
 
+/// Pings sent to keep a connection open, and how long it may stay silent before it is replaced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeepAlive {
+    pub ping_interval: Duration,
+    /// Without any frame for this long, pongs included, the connection is dropped and reconnected
+    pub stale_after: Duration,
+}
+
+impl Default for KeepAlive {
+    fn default() -> Self {
+        KeepAlive { ping_interval: Duration::from_secs(30), stale_after: Duration::from_secs(90) }
+    }
+}
+
+/// Levels of the partial book depth streams
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthLevels {
+    Five,
+    Ten,
+    Twenty,
+}
+
+impl DepthLevels {
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            DepthLevels::Five => 5,
+            DepthLevels::Ten => 10,
+            DepthLevels::Twenty => 20,
+        }
+    }
+}
+
+/// How often the depth streams push an update
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UpdateSpeed {
+    #[default]
+    Ms1000,
+    Ms100,
+}
+
+/// Windows of the rolling window ticker streams
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RollingWindow {
+    OneHour,
+    FourHours,
+    OneDay,
+}
+
+impl RollingWindow {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RollingWindow::OneHour => "1h",
+            RollingWindow::FourHours => "4h",
+            RollingWindow::OneDay => "1d",
+        }
+    }
+}
+
+impl UpdateSpeed {
+    fn stream_suffix(&self) -> &'static str {
+        match self {
+            UpdateSpeed::Ms1000 => "",
+            UpdateSpeed::Ms100 => "@100ms",
+        }
+    }
+}
+
 pub enum WebsocketStreamType {
     BookTicker(Vec<String>),
     AggregatedTrades(Vec<String>),
     IndividualTrade(Vec<String>),
-    PartialBookDepthStream(Vec<String>),
+    /// `<symbol>@depth<levels>[@100ms]`, the top `levels` bids and asks
+    PartialBookDepthStream { symbols: Vec<String>, levels: DepthLevels, speed: UpdateSpeed },
     TwentyFourHourTicker(Vec<String>),
     Kline { symbols: Vec<String>, interval: KlineInterval },
-    DiffDepthStream(Vec<String>),
+    /// `<symbol>@depth[@100ms]`, the changes to the order book
+    DiffDepthStream { symbols: Vec<String>, speed: UpdateSpeed },
     DayTickerAll,
+    /// `!bookTicker`, the best bid and ask of every symbol, as `WebsocketEvent::BookTicker`
+    BookTickerAll,
+    MiniTicker(Vec<String>),
+    MiniTickerAll,
+    /// `<symbol>@ticker_<window>`
+    RollingWindowTicker { symbols: Vec<String>, window: RollingWindow },
+    /// `!ticker_<window>@arr`
+    RollingWindowTickerAll(RollingWindow),
+    AveragePrice(Vec<String>),
+    /// `<symbol>@kline_<interval>@<timezone>`, klines opening and closing in a timezone like `+08:00`
+    KlineWithTimezone { symbols: Vec<String>, interval: KlineInterval, timezone: String },
     UserStream(String),
 }
 
@@ -115,29 +218,55 @@ pub enum UserDataStreamType {
 }
 
 impl WebsocketStreamType {
-    pub fn first(&mut self) -> String {
+    /// The first symbol, or the stream name of the all market streams
+    pub fn first(&mut self) -> Result<String, BinanceErr> {
+        match self {
+            WebsocketStreamType::DayTickerAll => Ok("!ticker@arr".into()),
+            WebsocketStreamType::BookTickerAll => Ok(BOOK_TICKER_ALL_STREAM.into()),
+            WebsocketStreamType::MiniTickerAll => Ok("!miniTicker@arr".into()),
+            WebsocketStreamType::RollingWindowTickerAll(window) => Ok(format!("!ticker_{}@arr", window.as_str())),
+            WebsocketStreamType::UserStream(listen_key) => Ok(listen_key.clone()),
+            stream_type => stream_type.symbols().first().cloned().ok_or_else(no_symbols),
+        }
+    }
+    /// Symbols the stream is about, none for the all market and user data streams
+    pub fn symbols(&self) -> &[String] {
         match self {
-            WebsocketStreamType::AggregatedTrades(s) => s[0].clone(),
-            WebsocketStreamType::IndividualTrade(s) => s[0].clone(),
-            WebsocketStreamType::PartialBookDepthStream(s) => s[0].clone(),
-            WebsocketStreamType::TwentyFourHourTicker(s) => s[0].clone(),
-            WebsocketStreamType::DayTickerAll => format!("!ticker@arr"),
-            WebsocketStreamType::Kline { symbols: s, .. } => s[0].clone(),
-            WebsocketStreamType::DiffDepthStream(s) => s[0].clone(),
-            WebsocketStreamType::BookTicker(s) => s[0].clone(),
-            WebsocketStreamType::UserStream(listen_key) => listen_key.clone()
+            WebsocketStreamType::AggregatedTrades(s)
+            | WebsocketStreamType::IndividualTrade(s)
+            | WebsocketStreamType::PartialBookDepthStream { symbols: s, .. }
+            | WebsocketStreamType::TwentyFourHourTicker(s)
+            | WebsocketStreamType::Kline { symbols: s, .. }
+            | WebsocketStreamType::DiffDepthStream { symbols: s, .. }
+            | WebsocketStreamType::BookTicker(s)
+            | WebsocketStreamType::MiniTicker(s)
+            | WebsocketStreamType::RollingWindowTicker { symbols: s, .. }
+            | WebsocketStreamType::AveragePrice(s)
+            | WebsocketStreamType::KlineWithTimezone { symbols: s, .. } => s,
+            WebsocketStreamType::DayTickerAll
+            | WebsocketStreamType::BookTickerAll
+            | WebsocketStreamType::MiniTickerAll
+            | WebsocketStreamType::RollingWindowTickerAll(_)
+            | WebsocketStreamType::UserStream(_) => &[]
         }
     }
     pub fn len(&mut self) -> usize {
         match self {
             WebsocketStreamType::AggregatedTrades(s) => s.len(),
             WebsocketStreamType::IndividualTrade(s) => s.len(),
-            WebsocketStreamType::PartialBookDepthStream(s) => s.len(),
+            WebsocketStreamType::PartialBookDepthStream { symbols: s, .. } => s.len(),
             WebsocketStreamType::TwentyFourHourTicker(s) => s.len(),
             WebsocketStreamType::DayTickerAll => 1,
             WebsocketStreamType::Kline { symbols: s, .. } => s.len(),
-            WebsocketStreamType::DiffDepthStream(s) => s.len(),
+            WebsocketStreamType::DiffDepthStream { symbols: s, .. } => s.len(),
             WebsocketStreamType::BookTicker(s) => s.len(),
+            WebsocketStreamType::BookTickerAll => 1,
+            WebsocketStreamType::MiniTicker(s) => s.len(),
+            WebsocketStreamType::MiniTickerAll => 1,
+            WebsocketStreamType::RollingWindowTicker { symbols: s, .. } => s.len(),
+            WebsocketStreamType::RollingWindowTickerAll(_) => 1,
+            WebsocketStreamType::AveragePrice(s) => s.len(),
+            WebsocketStreamType::KlineWithTimezone { symbols: s, .. } => s.len(),
             WebsocketStreamType::UserStream(_) => 1
         }
     }
@@ -152,98 +281,200 @@ pub enum WebsocketEvent {
     DayTickerAll(Vec<DayTickerEvent>),
     Kline(KlineEvent),
     DiffDepthStream(DepthOrderBookEvent),
-    PartialBookDepthStream(OrderBook),
+    /// The payload does not name the symbol, it is taken from the stream name (empty on a raw `/ws` connection)
+    PartialBookDepthStream { symbol: String, order_book: OrderBook },
     BookTicker(BookTickerEvent),
+    MiniTicker(MiniTickerEvent),
+    MiniTickerAll(Vec<MiniTickerEvent>),
+    RollingWindowTicker(RollingWindowTickerEvent),
+    RollingWindowTickerAll(Vec<RollingWindowTickerEvent>),
+    AveragePrice(AveragePriceEvent),
 
+    /// Retired by Binance, see `AccountPosition`
     AccountUpdate(AccountUpdateEvent),
+    AccountPosition(AccountPositionEvent),
     OrderUpdate(OrderTradeEvent),
+    ListStatus(ListStatusEvent),
     BalanceUpdate(BalanceUpdateEvent),
+    ExternalLockUpdate(ExternalLockUpdateEvent),
+    ListenKeyExpired(ListenKeyExpiredEvent),
+
+    /// The connection of a subscription dropped, events may be missed until it is `Reconnected`
+    Disconnected { subscription: Uuid, reason: String },
+    /// Binance acknowledged the subscription again after a reconnect, state built from its events
+    /// should be resynced. A rejected subscription ends the stream with an `Err` instead.
+    Reconnected { subscription: Uuid },
+    /// What `UserStream` did to keep the listen key of the subscription valid
+    ListenKey(ListenKeyEvent),
+    /// An event this crate does not parse (yet), as received
+    Raw { stream: Option<String>, data: serde_json::Value },
     None,
 }
 
-const STREAM: &'static str = "stream";
+// Event types, the `e` field of every payload that has one
+const AGGREGATED_TRADE: &str = "aggTrade";
+const INDIVIDUAL_TRADE: &str = "trade";
+const DIFF_DEPTH_ORDER_BOOK: &str = "depthUpdate";
+const KLINE: &str = "kline";
+const TWENTY_FOUR_HOUR_TICKER: &str = "24hrTicker";
+const MINI_TICKER: &str = "24hrMiniTicker";
+const ROLLING_WINDOW_TICKERS: [&str; 3] = ["1hTicker", "4hTicker", "1dTicker"];
+const AVERAGE_PRICE: &str = "avgPrice";
 
-const AGGREGATED_TRADE: &'static str = "aggTrade";
-const INDIVIDUAL_TRADE: &'static str = "trade";
-const DIFF_DEPTH_ORDER_BOOK: &'static str = "depthUpdate";
-const KLINE: &'static str = "kline";
-const PARTIAL_DEPTH_ORDER_BOOK: &'static str = "lastUpdateId";
-const TWENTY_FOUR_HOUR_TICKER: &'static str = "24hrTicker";
+const ACCOUNT_UPDATE: &str = "outboundAccountInfo";
+const ACCOUNT_POSITION: &str = "outboundAccountPosition";
+const ORDER_UPDATE: &str = "executionReport";
+const LIST_STATUS: &str = "listStatus";
+const BALANCE_UPDATE: &str = "balanceUpdate";
+const EXTERNAL_LOCK_UPDATE: &str = "externalLockUpdate";
+const LISTEN_KEY_EXPIRED: &str = "listenKeyExpired";
 
-const ACCOUNT_UPDATE: &'static str = "outboundAccountInfo";
-const ORDER_UPDATE: &'static str = "executionReport";
-const BALANCE_UPDATE: &'static str = "balanceUpdate";
+// Stream kinds whose payloads have no `e` field
+const BOOK_TICKER_STREAM: &str = "bookTicker";
+const PARTIAL_DEPTH_STREAM: &str = "depth";
+const BOOK_TICKER_ALL_STREAM: &str = "!bookTicker";
 
-#[pin_project]
-#[derive(Default)]
+/// Binance accepts at most 1024 streams on one connection
+pub const MAX_STREAMS_PER_CONNECTION: usize = 1024;
+/// and 5 incoming messages per second, beyond which it closes the connection
+pub const MAX_MESSAGES_PER_SECOND: usize = 5;
+
+/// Subscriptions share as few connections as the Binance limits allow, one with more streams than
+/// a connection carries is spread over several. Every connection is
+/// read by its own task, which reconnects and subscribes again whenever the connection drops
+/// (Binance closes every connection after 24 hours).
 pub struct Websocket {
-    subscribe_single_value: Option<String>,
-    subscriptions: HashMap<Uuid, usize>,
-    tokens: HashMap<usize, Uuid>,
-    #[pin]
-    streams: StreamUnordered<StoredStream>,
+    ws_endpoint: String,
+    reconnect_policy: RetryPolicy,
+    keepalive: KeepAlive,
+    max_streams_per_connection: usize,
+    max_messages_per_second: usize,
+    symbol_registry: Option<SymbolRegistry>,
+    connections: HashMap<u64, ConnectionHandle>,
+    next_connection_id: u64,
+    subscriptions: HashMap<Uuid, Subscription>,
+    subscribers: Subscribers,
+    last_messages: LastMessages,
+}
+
+struct Subscription {
+    /// The streams of the subscription, by the connection carrying them
+    streams: HashMap<u64, Vec<String>>,
+}
+
+impl Subscription {
+    fn all_streams(&self) -> Vec<String> {
+        self.streams.values().flatten().cloned().collect()
+    }
+}
+
+struct ConnectionHandle {
+    commands: mpsc::UnboundedSender<Command>,
+    task: JoinHandle<()>,
+    streams: HashSet<String>,
+    /// Shared with other subscriptions, unlike the one of a user data stream
+    shared: bool,
+    /// When the last frames were sent, to stay under `max_messages_per_second`
+    sent: VecDeque<Instant>,
+}
+
+impl ConnectionHandle {
+    /// How long until another frame can be sent without exceeding `max_messages` in a second
+    fn wait_for_budget(&mut self, max_messages: usize) -> Option<Duration> {
+        while self.sent.front().is_some_and(|sent| sent.elapsed() >= Duration::from_secs(1)) {
+            self.sent.pop_front();
+        }
+        if self.sent.len() < max_messages.max(1) {
+            return None;
+        }
+        self.sent.front().map(|sent| Duration::from_secs(1).saturating_sub(sent.elapsed()))
+    }
+}
+
+impl Default for Websocket {
+    fn default() -> Self {
+        Websocket::new_with_endpoint(Config::default().ws_endpoint)
+    }
 }
 
 impl Websocket {
     pub fn new() -> Self {
         Websocket::default()
     }
-    pub fn parse_response_type(&mut self, msg: &str) -> Result<WebsocketEvent, BinanceErr> {
-        let value: serde_json::Value = serde_json::from_str(msg)?;
-        return Ok(if msg.find(STREAM) != None {
-            if value["data"] != serde_json::Value::Null {
-                let data = format!("{}", value["data"]);
-                self.parse_response_type(&data)?
-            } else {
-                return Err(BinanceErr::from_str(format!("Websocket closed!")));
-            }
-        } else if value["u"] != serde_json::Value::Null
-            && value["s"] != serde_json::Value::Null
-            && value["b"] != serde_json::Value::Null
-            && value["B"] != serde_json::Value::Null
-            && value["a"] != serde_json::Value::Null
-            && value["A"] != serde_json::Value::Null
-        {
-            let book_ticker: BookTickerEvent = serde_json::from_str(msg)?;
-            WebsocketEvent::BookTicker(book_ticker)
-        } else if msg.find(INDIVIDUAL_TRADE) != None {
-            let trade: OneTradeEvent = serde_json::from_str(msg)?;
-            WebsocketEvent::IndividualTrade(trade)
-        } else if msg.find(ACCOUNT_UPDATE) != None {
-            let account_update: AccountUpdateEvent = serde_json::from_str(msg)?;
-            WebsocketEvent::AccountUpdate(account_update)
-        } else if msg.find(BALANCE_UPDATE) != None {
-            let balance_update: BalanceUpdateEvent = serde_json::from_str(msg)?;
-            WebsocketEvent::BalanceUpdate(balance_update)
-        } else if msg.find(ORDER_UPDATE) != None {
-            let order_trade: OrderTradeEvent = serde_json::from_str(msg)?;
-            WebsocketEvent::OrderUpdate(order_trade)
-        } else if msg.find(AGGREGATED_TRADE) != None {
-            let trade: TradesEvent = serde_json::from_str(msg)?;
-            WebsocketEvent::AggregatedTrades(trade)
-        } else if msg.find(TWENTY_FOUR_HOUR_TICKER) != None {
-            if let Some(single_value) = &self.subscribe_single_value {
-                if single_value == "!ticker@arr" {
-                    let trades: Vec<DayTickerEvent> = serde_json::from_str(msg)?;
-                    return Ok(WebsocketEvent::DayTickerAll(trades));
-                }
-            }
-            let trades: DayTickerEvent = serde_json::from_str(msg)?;
-            WebsocketEvent::TwentyFourHourTicker(trades)
-        } else if msg.find(KLINE) != None {
-            let kline: KlineEvent = serde_json::from_str(msg)?;
-            WebsocketEvent::Kline(kline)
-        } else if msg.find(PARTIAL_DEPTH_ORDER_BOOK) != None {
-            let partial_orderbook: OrderBook = serde_json::from_str(msg)?;
-            WebsocketEvent::PartialBookDepthStream(partial_orderbook)
-        } else if msg.find(DIFF_DEPTH_ORDER_BOOK) != None {
-            let depth_orderbook: DepthOrderBookEvent = serde_json::from_str(msg)?;
-            WebsocketEvent::DiffDepthStream(depth_orderbook)
+    pub fn new_with_endpoint(ws_endpoint: String) -> Self {
+        Websocket {
+            ws_endpoint,
+            reconnect_policy: RetryPolicy::reconnect(),
+            keepalive: KeepAlive::default(),
+            max_streams_per_connection: MAX_STREAMS_PER_CONNECTION,
+            max_messages_per_second: MAX_MESSAGES_PER_SECOND,
+            symbol_registry: None,
+            connections: HashMap::new(),
+            next_connection_id: 1,
+            subscriptions: HashMap::new(),
+            subscribers: Subscribers::default(),
+            last_messages: LastMessages::default(),
+        }
+    }
+    pub fn new_with_config(config: &Config) -> Self {
+        Websocket::new_with_endpoint(config.ws_endpoint.clone())
+            .set_reconnect_policy(config.reconnect_policy.clone())
+            .set_keepalive(config.keepalive)
+    }
+    /// How subscriptions reconnect when their connection drops
+    pub fn set_reconnect_policy(mut self, reconnect_policy: RetryPolicy) -> Self {
+        self.reconnect_policy = reconnect_policy;
+        self
+    }
+    /// How often connections are pinged, and after how long without a frame they are reconnected
+    pub fn set_keepalive(mut self, keepalive: KeepAlive) -> Self {
+        self.keepalive = keepalive;
+        self
+    }
+    /// At most the Binance limits: another connection is opened rather than carry more streams,
+    /// and frames wait rather than exceed the messages per second of a connection
+    pub fn set_connection_limits(mut self, max_streams: usize, max_messages_per_second: usize) -> Self {
+        self.max_streams_per_connection = max_streams.min(MAX_STREAMS_PER_CONNECTION);
+        self.max_messages_per_second = max_messages_per_second.min(MAX_MESSAGES_PER_SECOND);
+        self
+    }
+    /// Number of connections currently open
+    pub fn connection_count(&self) -> usize {
+        self.connections.len()
+    }
+    /// When the subscription last received an event, `None` before the first one
+    pub fn last_message_at(&self, uuid: Uuid) -> Option<Instant> {
+        self.last_messages.lock().unwrap_or_else(|e| e.into_inner()).get(&uuid).copied()
+    }
+    /// Streams Binance reports as subscribed (LIST_SUBSCRIPTIONS), over every connection
+    pub async fn list_subscriptions(&mut self) -> Result<Vec<String>, BinanceErr> {
+        let mut streams = Vec::new();
+        let ids: Vec<u64> = self.connections.keys().copied().collect();
+        for id in ids {
+            let result = self.request(id, |ack| Command::ListSubscriptions { ack }).await?;
+            streams.extend(serde_json::from_value::<Option<Vec<String>>>(result)?.unwrap_or_default());
+        }
+        Ok(streams)
+    }
+    /// Check the symbols of every subscription against `registry`, so a typo fails right away
+    /// instead of subscribing to a stream that never sends anything
+    pub fn with_symbol_registry(mut self, registry: SymbolRegistry) -> Self {
+        self.symbol_registry = Some(registry);
+        self
+    }
+    /// Another consumer of the events of a subscription, `None` if it is unknown
+    pub fn events(&self, uuid: Uuid) -> Option<SubscriptionStream> {
+        if self.subscriptions.contains_key(&uuid) {
+            Some(SubscriptionStream::new(uuid, &self.subscribers))
         } else {
-            WebsocketEvent::None
-        });
+            None
+        }
+    }
+    /// Parse a text frame, either a combined stream `{"stream", "data"}` wrapper or a raw payload
+    pub fn parse_response_type(&self, msg: &str) -> Result<WebsocketEvent, BinanceErr> {
+        parse_text(msg).map(|(_, event)| event)
     }
-    pub fn parse_message(&mut self, msg: Message) -> Result<WebsocketEvent, BinanceErr> {
+    pub fn parse_message(&self, msg: Message) -> Result<WebsocketEvent, BinanceErr> {
         return match msg {
             Message::Text(msg) => self.parse_response_type(&msg),
             Message::Ping(_) | Message::Pong(_) | Message::Binary(_) => Ok(WebsocketEvent::None),
@@ -252,65 +483,413 @@ impl Websocket {
     }
 }
 
-const WEBSOCKET_BINANCE_URL: &'static str = "wss://stream.binance.com:9443/stream";
-
 #[async_trait::async_trait]
 pub trait WebsocketAsync {
-    async fn subscribe(&mut self, endpoint: WebsocketStreamType) -> Result<Uuid, BinanceErr>;
-    fn unsubscribe(&mut self, uuid: Uuid) -> Option<StoredStream>;
+    /// The events of the new subscription, `SubscriptionStream::id` identifies it
+    async fn subscribe(&mut self, endpoint: WebsocketStreamType) -> Result<SubscriptionStream, BinanceErr>;
+    /// Unsubscribe from every stream of the subscription, `false` if it is unknown
+    async fn unsubscribe(&mut self, uuid: Uuid) -> Result<bool, BinanceErr>;
+    /// Unsubscribe from the streams of some of the symbols of the subscription only
+    async fn unsubscribe_symbols(&mut self, uuid: Uuid, symbols: &[String]) -> Result<bool, BinanceErr>;
 }
 
 #[async_trait::async_trait]
 impl WebsocketAsync for Websocket {
-    async fn subscribe(&mut self, mut stream_type: WebsocketStreamType) -> Result<Uuid, BinanceErr> {
-        self.subscribe_single_value = if stream_type.len() == 1 {
-            Some(stream_type.first())
-        } else {
-            None
+    async fn subscribe(&mut self, stream_type: WebsocketStreamType) -> Result<SubscriptionStream, BinanceErr> {
+        if let Some(registry) = &self.symbol_registry {
+            for symbol in stream_type.symbols() {
+                registry.get_symbol(symbol).await?;
+            }
+        }
+        let uuid = Uuid::new_v4();
+        // Registered first, so nothing sent right after subscribing is missed
+        let events = SubscriptionStream::new(uuid, &self.subscribers);
+        let streams = match stream_type {
+            // The user data stream is addressed by its listen key, on a connection of its own
+            WebsocketStreamType::UserStream(listen_key) => {
+                if let Err(e) = self.open_user_stream(uuid, listen_key).await {
+                    self.forget(uuid);
+                    return Err(e);
+                }
+                return Ok(events);
+            }
+            stream_type => ExchangeSettings::map_symbols_to_stream_params(stream_type)
         };
-        let url = Url::parse(&WEBSOCKET_BINANCE_URL).unwrap();
-        let (ws_stream, _) = connect_async(url).await?;
-
-        let (mut sink, read) = ws_stream.split();
+        if streams.is_empty() {
+            self.forget(uuid);
+            return Err(no_symbols());
+        }
 
-        if let WebsocketStreamType::UserStream(_) = stream_type {} else {
-            let sub = ExchangeSettings::from(stream_type);
-            sink.send(Message::Text(serde_json::to_string(&sub)?)).await?;
+        let placed = match self.place_streams(&streams).await {
+            Ok(placed) => placed,
+            Err(e) => {
+                self.forget(uuid);
+                return Err(e);
+            }
+        };
+        self.subscriptions.insert(uuid, Subscription { streams: placed.clone() });
+        for (connection, streams) in placed {
+            let subscribe = self.request(connection, |ack| Command::Subscribe { subscription: uuid, streams, ack }).await;
+            if let Err(e) = subscribe {
+                self.unsubscribe(uuid).await.ok();
+                return Err(e);
+            }
         }
+        Ok(events)
+    }
 
-        let uuid = Uuid::new_v4();
-        let token = self.streams.insert(read);
-        self.subscriptions.insert(uuid, token);
-        self.tokens.insert(token, uuid);
-        Ok(uuid)
+    async fn unsubscribe(&mut self, uuid: Uuid) -> Result<bool, BinanceErr> {
+        let streams = match self.subscriptions.get(&uuid) {
+            Some(subscription) => subscription.all_streams(),
+            None => return Ok(false)
+        };
+        self.remove_streams(uuid, streams).await?;
+        Ok(true)
     }
 
-    fn unsubscribe(&mut self, uuid: Uuid) -> Option<StoredStream> {
-        let streams = Pin::new(&mut self.streams);
-        self.subscriptions
-            .get(&uuid)
-            .and_then(|token| StreamUnordered::take(streams, *token))
+    async fn unsubscribe_symbols(&mut self, uuid: Uuid, symbols: &[String]) -> Result<bool, BinanceErr> {
+        let symbols: HashSet<String> = symbols.iter().map(|symbol| symbol.to_lowercase()).collect();
+        let streams = match self.subscriptions.get(&uuid) {
+            Some(subscription) => subscription
+                .all_streams()
+                .into_iter()
+                .filter(|stream| stream.split('@').next().is_some_and(|symbol| symbols.contains(symbol)))
+                .collect(),
+            None => return Ok(false)
+        };
+        self.remove_streams(uuid, streams).await?;
+        Ok(true)
     }
 }
 
-impl Stream for Websocket {
-    type Item = Result<WebsocketEvent, BinanceErr>;
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        match self.as_mut().project().streams.poll_next(cx) {
-            Poll::Ready(Some((y, _))) => match y {
-                StreamYield::Item(item) => {
-                    Poll::Ready({
-                        Some(
-                            item.map_err(BinanceErr::Websocket)
-                                .and_then(|m| self.parse_message(m)),
-                        )
+impl Websocket {
+    /// Stop routing `streams` to the subscription, ended once it has none left. Binance is told
+    /// to unsubscribe from the ones no other subscription uses, and a connection left without
+    /// subscriptions is closed.
+    async fn remove_streams(&mut self, uuid: Uuid, streams: Vec<String>) -> Result<(), BinanceErr> {
+        let removed: Vec<(u64, Vec<String>)> = match self.subscriptions.get_mut(&uuid) {
+            Some(subscription) => {
+                let removed = subscription
+                    .streams
+                    .iter_mut()
+                    .map(|(connection, carried)| {
+                        let (removed, kept) = carried.drain(..).partition(|stream| streams.contains(stream));
+                        *carried = kept;
+                        (*connection, removed)
                     })
+                    .filter(|(_, removed): &(u64, Vec<String>)| !removed.is_empty())
+                    .collect();
+                subscription.streams.retain(|_, carried| !carried.is_empty());
+                if subscription.streams.is_empty() {
+                    self.forget(uuid);
                 }
-                StreamYield::Finished(_) => Poll::Pending,
-            },
-            Poll::Ready(None) => panic!("No Stream Subscribed"),
-            Poll::Pending => Poll::Pending,
+                removed
+            }
+            None => return Ok(())
+        };
+
+        let mut result = Ok(());
+        for (connection, streams) in removed {
+            let still_used: HashSet<&String> =
+                self.subscriptions.values().filter_map(|s| s.streams.get(&connection)).flatten().collect();
+            let in_use = !still_used.is_empty();
+            if let Some(handle) = self.connections.get_mut(&connection) {
+                handle.streams.retain(|stream| still_used.contains(stream));
+            }
+
+            let unsubscribe = self.request(connection, |ack| Command::Unsubscribe { subscription: uuid, streams, ack }).await;
+            if !in_use {
+                // Dropping the commands sender lets the task close the connection
+                self.connections.remove(&connection);
+            }
+            // The other connections are still told
+            if let Err(e) = unsubscribe {
+                result = Err(e);
+            }
         }
+        result
+    }
+
+    /// Move a user data subscription to a connection for a new listen key, its consumers keep
+    /// receiving events from the same streams
+    pub(crate) async fn replace_listen_key(&mut self, uuid: Uuid, listen_key: String) -> Result<(), BinanceErr> {
+        let expired = self
+            .subscriptions
+            .get(&uuid)
+            .and_then(|subscription| subscription.streams.keys().next().copied())
+            .ok_or_else(|| BinanceErr::from_str("Unknown user data subscription".into()))?;
+        self.open_user_stream(uuid, listen_key).await?;
+        // Aborted rather than let go of, so it does not end the subscription when its reconnecting fails
+        if let Some(handle) = self.connections.remove(&expired) {
+            handle.task.abort();
+        }
+        Ok(())
+    }
+
+    /// Tell every consumer of the subscription what happened to its listen key
+    pub(crate) fn notify(&self, uuid: Uuid, event: ListenKeyEvent) {
+        if let Some(senders) = self.subscribers.lock().unwrap_or_else(|e| e.into_inner()).get_mut(&uuid) {
+            senders.retain(|sender| sender.send(ConnectionEvent::ListenKey(event.clone())).is_ok());
+        }
+    }
+
+    /// The user data stream is addressed by its listen key, on a connection of its own
+    async fn open_user_stream(&mut self, uuid: Uuid, listen_key: String) -> Result<(), BinanceErr> {
+        let url = format!("{}?streams={}", self.ws_endpoint, listen_key);
+        let connection = self.open_connection(&url, Some(uuid), false).await?;
+        if let Some(handle) = self.connections.get_mut(&connection) {
+            handle.streams.insert(listen_key.clone());
+        }
+        self.subscriptions.insert(uuid, Subscription { streams: HashMap::from([(connection, vec![listen_key])]) });
+        Ok(())
+    }
+
+    /// Drop a subscription, ending the streams of its consumers
+    fn forget(&mut self, uuid: Uuid) {
+        self.subscriptions.remove(&uuid);
+        self.subscribers.lock().unwrap_or_else(|e| e.into_inner()).remove(&uuid);
+        self.last_messages.lock().unwrap_or_else(|e| e.into_inner()).remove(&uuid);
+    }
+
+    /// Spread `streams` over the shared connections: a stream already carried stays on its
+    /// connection, the others fill the connections with room and then as many new ones as needed
+    async fn place_streams(&mut self, streams: &[String]) -> Result<HashMap<u64, Vec<String>>, BinanceErr> {
+        // Connections that gave up reconnecting, along with their subscriptions
+        let failed: Vec<u64> = self.connections.iter().filter(|(_, handle)| handle.task.is_finished()).map(|(id, _)| *id).collect();
+        for connection in failed {
+            self.connections.remove(&connection);
+            let subscriptions: Vec<Uuid> =
+                self.subscriptions.iter().filter(|(_, s)| s.streams.contains_key(&connection)).map(|(uuid, _)| *uuid).collect();
+            subscriptions.into_iter().for_each(|uuid| self.forget(uuid));
+        }
+
+        let mut placed: HashMap<u64, Vec<String>> = HashMap::new();
+        let mut unplaced = Vec::new();
+        for stream in streams {
+            match self.connections.iter().find(|(_, handle)| handle.shared && handle.streams.contains(stream)) {
+                Some((id, _)) => placed.entry(*id).or_default().push(stream.clone()),
+                None => unplaced.push(stream.clone()),
+            }
+        }
+        let max_streams = self.max_streams_per_connection.max(1);
+        let mut shared: Vec<(u64, usize)> =
+            self.connections.iter().filter(|(_, handle)| handle.shared).map(|(id, handle)| (*id, handle.streams.len())).collect();
+        shared.sort_unstable();
+        for (id, carried) in shared {
+            let taken = max_streams.saturating_sub(carried).min(unplaced.len());
+            if taken > 0 {
+                placed.entry(id).or_default().extend(unplaced.drain(..taken));
+            }
+        }
+
+        let mut opened = Vec::new();
+        while !unplaced.is_empty() {
+            let url = self.ws_endpoint.clone();
+            let id = match self.open_connection(&url, None, true).await {
+                Ok(id) => id,
+                Err(e) => {
+                    opened.iter().for_each(|id| {
+                        self.connections.remove(id);
+                    });
+                    return Err(e);
+                }
+            };
+            opened.push(id);
+            let taken = max_streams.min(unplaced.len());
+            placed.insert(id, unplaced.drain(..taken).collect());
+        }
+        Ok(placed)
+    }
+
+    async fn open_connection(&mut self, url: &str, subscription: Option<Uuid>, shared: bool) -> Result<u64, BinanceErr> {
+        let url = Url::parse(url).map_err(|e| BinanceErr::from_str(format!("Invalid websocket endpoint: {}", e)))?;
+        let (connection, ws_stream) =
+            Connection::open(
+                url,
+                subscription,
+                self.reconnect_policy.clone(),
+                self.keepalive,
+                self.subscribers.clone(),
+                self.last_messages.clone(),
+            )
+            .await?;
+        let (commands, receiver) = mpsc::unbounded_channel();
+        let handle = ConnectionHandle {
+            commands,
+            task: tokio::spawn(connection.run(ws_stream, receiver)),
+            streams: HashSet::new(),
+            shared,
+            sent: VecDeque::new(),
+        };
+        let id = self.next_connection_id;
+        self.next_connection_id += 1;
+        self.connections.insert(id, handle);
+        Ok(id)
+    }
+
+    /// Send a command to a connection, once its messages per second allow it, and wait for Binance to answer it
+    async fn request<F: FnOnce(Ack) -> Command>(&mut self, connection: u64, command: F) -> Result<serde_json::Value, BinanceErr> {
+        let max_messages = self.max_messages_per_second;
+        let handle = loop {
+            let handle = self
+                .connections
+                .get_mut(&connection)
+                .ok_or_else(|| BinanceErr::from_str("Websocket connection closed".into()))?;
+            match handle.wait_for_budget(max_messages) {
+                Some(wait) => tokio::time::sleep(wait).await,
+                None => break handle,
+            }
+        };
+        let (ack, response) = oneshot::channel();
+        let command = command(ack);
+        if let Command::Subscribe { streams, .. } = &command {
+            handle.streams.extend(streams.iter().cloned());
+        }
+        handle.sent.push_back(Instant::now());
+        handle
+            .commands
+            .send(command)
+            .map_err(|_| BinanceErr::from_str("Websocket connection closed".into()))?;
+        response
+            .await
+            .map_err(|_| BinanceErr::from_str("Websocket connection closed".into()))?
+    }
+}
+
+/// A combined stream `{"stream", "data"}` wrapper or a raw payload, with the stream name if there is one.
+/// A payload that does not parse is a `BinanceErr::MalformedPayload` carrying the frame as received.
+fn parse_text(msg: &str) -> Result<(Option<String>, WebsocketEvent), BinanceErr> {
+    let parsed = serde_json::from_str(msg).map_err(BinanceErr::from).and_then(|value: serde_json::Value| {
+        match (value.get("stream").and_then(|s| s.as_str()), value.get("data")) {
+            (Some(stream), Some(data)) => Ok((Some(stream.to_string()), parse_event(Some(stream), data.clone())?)),
+            _ => Ok((None, parse_event(None, value)?)),
+        }
+    });
+    parsed.map_err(|e| match e {
+        BinanceErr::Serde(e) => BinanceErr::MalformedPayload { msg: e.to_string(), payload: msg.into(), endpoint: None },
+        e => e,
+    })
+}
+
+/// Dispatch on the event type, or on the stream name for the payloads without one.
+/// Anything unrecognised is kept as `WebsocketEvent::Raw`.
+fn parse_event(stream: Option<&str>, data: serde_json::Value) -> Result<WebsocketEvent, BinanceErr> {
+    // e.g. `ticker` for `btcusdt@ticker`, `depth5` for `btcusdt@depth5@100ms`
+    let stream_kind = stream.and_then(|stream| stream.split('@').nth(1)).unwrap_or("");
+
+    if let serde_json::Value::Array(items) = &data {
+        let event_type = items.first().and_then(|item| item.get("e")).and_then(|e| e.as_str());
+        return Ok(match event_type {
+            Some(TWENTY_FOUR_HOUR_TICKER) => WebsocketEvent::DayTickerAll(serde_json::from_value(data)?),
+            Some(MINI_TICKER) => WebsocketEvent::MiniTickerAll(serde_json::from_value(data)?),
+            Some(e) if ROLLING_WINDOW_TICKERS.contains(&e) => WebsocketEvent::RollingWindowTickerAll(serde_json::from_value(data)?),
+            _ => raw(stream, data),
+        });
+    }
+
+    let event_type = data.get("e").and_then(|e| e.as_str()).map(String::from);
+    Ok(match event_type.as_deref() {
+        Some(INDIVIDUAL_TRADE) => WebsocketEvent::IndividualTrade(serde_json::from_value(data)?),
+        Some(AGGREGATED_TRADE) => WebsocketEvent::AggregatedTrades(serde_json::from_value(data)?),
+        Some(TWENTY_FOUR_HOUR_TICKER) => WebsocketEvent::TwentyFourHourTicker(serde_json::from_value(data)?),
+        Some(KLINE) => WebsocketEvent::Kline(serde_json::from_value(data)?),
+        Some(MINI_TICKER) => WebsocketEvent::MiniTicker(serde_json::from_value(data)?),
+        Some(e) if ROLLING_WINDOW_TICKERS.contains(&e) => WebsocketEvent::RollingWindowTicker(serde_json::from_value(data)?),
+        Some(AVERAGE_PRICE) => WebsocketEvent::AveragePrice(serde_json::from_value(data)?),
+        Some(DIFF_DEPTH_ORDER_BOOK) => WebsocketEvent::DiffDepthStream(serde_json::from_value(data)?),
+        Some(ACCOUNT_UPDATE) => WebsocketEvent::AccountUpdate(serde_json::from_value(data)?),
+        Some(ACCOUNT_POSITION) => WebsocketEvent::AccountPosition(serde_json::from_value(data)?),
+        Some(ORDER_UPDATE) => WebsocketEvent::OrderUpdate(serde_json::from_value(data)?),
+        Some(LIST_STATUS) => WebsocketEvent::ListStatus(serde_json::from_value(data)?),
+        Some(BALANCE_UPDATE) => WebsocketEvent::BalanceUpdate(serde_json::from_value(data)?),
+        Some(EXTERNAL_LOCK_UPDATE) => WebsocketEvent::ExternalLockUpdate(serde_json::from_value(data)?),
+        Some(LISTEN_KEY_EXPIRED) => WebsocketEvent::ListenKeyExpired(serde_json::from_value(data)?),
+        Some(_) => raw(stream, data),
+        None if stream_kind == BOOK_TICKER_STREAM || stream == Some(BOOK_TICKER_ALL_STREAM) => WebsocketEvent::BookTicker(serde_json::from_value(data)?),
+        None if stream_kind.starts_with(PARTIAL_DEPTH_STREAM) => {
+            let symbol = stream.and_then(|stream| stream.split('@').next()).unwrap_or("").to_uppercase();
+            WebsocketEvent::PartialBookDepthStream { symbol, order_book: serde_json::from_value(data)? }
+        }
+        // A raw `/ws/<stream>` connection does not name the stream, recognise the payload instead
+        None if stream.is_none() && data.get("lastUpdateId").is_some() => {
+            WebsocketEvent::PartialBookDepthStream { symbol: String::new(), order_book: serde_json::from_value(data)? }
+        }
+        None if stream.is_none() && ["u", "s", "b", "B", "a", "A"].iter().all(|key| data.get(key).is_some()) => {
+            WebsocketEvent::BookTicker(serde_json::from_value(data)?)
+        }
+        None => raw(stream, data),
+    })
+}
+
+fn no_symbols() -> BinanceErr {
+    BinanceErr::from_str("No symbol to subscribe to".into())
+}
+
+fn raw(stream: Option<&str>, data: serde_json::Value) -> WebsocketEvent {
+    WebsocketEvent::Raw { stream: stream.map(String::from), data }
+}
+
+impl Drop for Websocket {
+    fn drop(&mut self) {
+        self.connections.values().for_each(|handle| handle.task.abort());
+    }
+}
+
+/// An event of a subscription, with the stream it came from
+#[derive(Debug)]
+pub struct SubscriptionEvent {
+    pub subscription: Uuid,
+    /// e.g. `btcusdt@trade`, `None` for the connection events and on a raw `/ws` connection
+    pub stream: Option<String>,
+    pub event: WebsocketEvent,
+}
+
+/// The events of one subscription. Each consumer (see `fan_out`) receives all of them,
+/// and the stream ends once the subscription is unsubscribed or gave up reconnecting.
+pub struct SubscriptionStream {
+    id: Uuid,
+    subscribers: Subscribers,
+    events: mpsc::UnboundedReceiver<ConnectionEvent>,
+}
+
+impl SubscriptionStream {
+    fn new(id: Uuid, subscribers: &Subscribers) -> Self {
+        let (sender, events) = mpsc::unbounded_channel();
+        subscribers.lock().unwrap_or_else(|e| e.into_inner()).entry(id).or_default().push(sender);
+        SubscriptionStream { id, subscribers: subscribers.clone(), events }
+    }
+    /// Identifies the subscription, e.g. to `unsubscribe` it
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+    /// Another consumer of the subscription, receiving its events from now on
+    pub fn fan_out(&self) -> SubscriptionStream {
+        SubscriptionStream::new(self.id, &self.subscribers)
+    }
+}
+
+impl Stream for SubscriptionStream {
+    type Item = Result<SubscriptionEvent, BinanceErr>;
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let event = match this.events.poll_recv(cx) {
+            Poll::Ready(Some(event)) => event,
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => return Poll::Pending,
+        };
+        let subscription = this.id;
+        let tagged = |event| SubscriptionEvent { subscription, stream: None, event };
+        Poll::Ready(Some(match event {
+            ConnectionEvent::Message(Message::Text(text)) => parse_text(&text)
+                .map(|(stream, event)| SubscriptionEvent { subscription, stream, event }),
+            ConnectionEvent::Message(_) => Ok(tagged(WebsocketEvent::None)),
+            ConnectionEvent::Disconnected(reason) => Ok(tagged(WebsocketEvent::Disconnected { subscription, reason })),
+            ConnectionEvent::Reconnected => Ok(tagged(WebsocketEvent::Reconnected { subscription })),
+            ConnectionEvent::ListenKey(event) => Ok(tagged(WebsocketEvent::ListenKey(event))),
+            ConnectionEvent::Failed(reason) => {
+                Err(BinanceErr::from_str(format!("Websocket reconnection given up: {}", reason)))
+            }
+        }))
     }
 }
 
@@ -318,9 +897,14 @@ impl Stream for Websocket {
 */
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use futures::TryStreamExt;
+    use serde_json::json;
 
     use crate::error::BinanceErr;
+    use crate::mock_server::MockBinanceServer;
+    use crate::order::{ExecutionType, OrderStatus};
     use crate::websocket::*;
 
     fn correct_symbol(res: WebsocketEvent) -> bool {
@@ -332,24 +916,291 @@ mod tests {
     }
 
     async fn test_binance_ws() -> Result<(), BinanceErr> {
-        let mut binance_ws = Websocket::new();
+        let server = MockBinanceServer::start().await?;
+        let mut binance_ws = Websocket::new_with_endpoint(server.ws_endpoint().into());
         let endpoints = vec!["ETHBTC".into(), "ADABTC".into()];
-        let sub_uuid = binance_ws.subscribe(WebsocketStreamType::IndividualTrade(endpoints)).await?;
-        for _ in 0..5 {
-            let res = binance_ws.try_next().await.expect("Didn't receive next transmit");
-            let res = res.expect("Got no match for trade type!");
+        let mut trades = binance_ws.subscribe(WebsocketStreamType::IndividualTrade(endpoints)).await?;
+        server.wait_for_subscription("adabtc@trade").await;
+        for (i, symbol) in ["ETHBTC", "ADABTC", "ETHBTC", "ADABTC", "ETHBTC"].iter().enumerate() {
+            server.push_event(format!("{}@trade", symbol.to_lowercase()), json!({
+                "e": "trade", "E": 123456789, "s": symbol, "t": i, "p": "0.001", "q": "100",
+                "b": 88, "a": 50, "T": 123456785, "m": true, "M": true
+            }));
+        }
+        let mut received = 0;
+        while received < 5 {
+            let res = trades.try_next().await?.expect("Didn't receive next transmit").event;
             if let WebsocketEvent::None = res {
                 continue;
             }
             assert!(correct_symbol(res));
+            received += 1;
         }
-        binance_ws.unsubscribe(sub_uuid);
+        assert!(binance_ws.unsubscribe(trades.id()).await?);
         Ok(())
     }
 
     #[tokio::main]
     #[test]
     async fn it_works() {
-        test_binance_ws().await.ok();
+        test_binance_ws().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reconnects_and_resubscribes_under_the_same_id() {
+        let server = MockBinanceServer::start().await.unwrap();
+        let reconnect_policy = RetryPolicy { initial_backoff: Duration::from_millis(10), ..RetryPolicy::reconnect() };
+        let mut ws = Websocket::new_with_endpoint(server.ws_endpoint().into()).set_reconnect_policy(reconnect_policy);
+        let mut trades = ws.subscribe(WebsocketStreamType::IndividualTrade(vec!["BTCUSDT".into()])).await.unwrap();
+        let id = trades.id();
+        server.wait_for_subscription("btcusdt@trade").await;
+
+        server.disconnect_websockets();
+        let mut events = Vec::new();
+        while events.len() < 2 {
+            match trades.try_next().await.unwrap().unwrap().event {
+                WebsocketEvent::None => continue,
+                event => events.push(event)
+            }
+        }
+        assert!(matches!(events[0], WebsocketEvent::Disconnected { subscription, .. } if subscription == id));
+        assert!(matches!(events[1], WebsocketEvent::Reconnected { subscription } if subscription == id));
+
+        server.wait_for_subscription("btcusdt@trade").await;
+        loop {
+            server.push_event("btcusdt@trade", json!({
+                "e": "trade", "E": 123456789, "s": "BTCUSDT", "t": 1, "p": "0.001", "q": "100",
+                "b": 88, "a": 50, "T": 123456785, "m": true, "M": true
+            }));
+            // The old connection may still be listed for a moment, so keep pushing until it arrives
+            if let Ok(event) = tokio::time::timeout(Duration::from_millis(100), trades.try_next()).await {
+                if let WebsocketEvent::IndividualTrade(trade) = event.unwrap().unwrap().event {
+                    assert_eq!(trade.symbol, "BTCUSDT");
+                    break;
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn a_subscription_rejected_after_reconnecting_fails() {
+        let server = MockBinanceServer::start().await.unwrap();
+        let reconnect_policy = RetryPolicy { initial_backoff: Duration::from_millis(10), ..RetryPolicy::reconnect() };
+        let mut ws = Websocket::new_with_endpoint(server.ws_endpoint().into()).set_reconnect_policy(reconnect_policy);
+        let mut trades = ws.subscribe(WebsocketStreamType::IndividualTrade(vec!["BTCUSDT".into()])).await.unwrap();
+        server.wait_for_subscription("btcusdt@trade").await;
+
+        server.reject_subscriptions(true);
+        server.disconnect_websockets();
+        assert!(matches!(trades.try_next().await.unwrap().unwrap().event, WebsocketEvent::Disconnected { .. }));
+        let error = trades.try_next().await.unwrap_err();
+        assert!(error.to_string().contains("subscription rejected"), "{}", error);
+        assert!(trades.try_next().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn pings_keep_quiet_connections_open_and_silent_ones_are_replaced() {
+        let server = MockBinanceServer::start().await.unwrap();
+        let keepalive = KeepAlive { ping_interval: Duration::from_millis(50), stale_after: Duration::from_millis(300) };
+        let mut ws = Websocket::new_with_endpoint(server.ws_endpoint().into()).set_keepalive(keepalive);
+        let mut trades = ws.subscribe(WebsocketStreamType::IndividualTrade(vec!["BTCUSDT".into()])).await.unwrap();
+        let id = trades.id();
+        server.wait_for_subscription("btcusdt@trade").await;
+        assert!(tokio::time::timeout(Duration::from_secs(1), trades.try_next()).await.is_err(), "the pongs kept it alive");
+        assert_eq!(ws.last_message_at(id), None);
+
+        server.push_event("btcusdt@trade", json!({
+            "e": "trade", "E": 123456789, "s": "BTCUSDT", "t": 1, "p": "0.001", "q": "100",
+            "b": 88, "a": 50, "T": 123456785, "m": true, "M": true
+        }));
+        assert!(matches!(trades.try_next().await.unwrap().unwrap().event, WebsocketEvent::IndividualTrade(_)));
+        assert!(ws.last_message_at(id).is_some());
+
+        // Never pinging, so nothing comes back
+        let keepalive = KeepAlive { ping_interval: Duration::from_secs(3600), stale_after: Duration::from_millis(200) };
+        let mut ws = Websocket::new_with_endpoint(server.ws_endpoint().into()).set_keepalive(keepalive);
+        let mut trades = ws.subscribe(WebsocketStreamType::IndividualTrade(vec!["BTCUSDT".into()])).await.unwrap();
+        assert!(matches!(trades.try_next().await.unwrap().unwrap().event, WebsocketEvent::Disconnected { subscription, reason }
+            if subscription == trades.id() && reason.starts_with("Nothing received")));
+    }
+
+    #[tokio::test]
+    async fn every_consumer_of_a_subscription_gets_only_its_events() {
+        let server = MockBinanceServer::start().await.unwrap();
+        let mut ws = Websocket::new_with_endpoint(server.ws_endpoint().into());
+        let mut trades = ws.subscribe(WebsocketStreamType::IndividualTrade(vec!["BTCUSDT".into()])).await.unwrap();
+        let mut tickers = ws.subscribe(WebsocketStreamType::BookTicker(vec!["BTCUSDT".into()])).await.unwrap();
+        let mut tickers_too = tickers.fan_out();
+        server.wait_for_subscription("btcusdt@bookTicker").await;
+
+        server.push_event("btcusdt@bookTicker", json!({"u": 1, "s": "BTCUSDT", "b": "1.0", "B": "2.0", "a": "1.1", "A": "3.0"}));
+        for consumer in [&mut tickers, &mut tickers_too] {
+            let event = consumer.try_next().await.unwrap().unwrap();
+            assert_eq!(event.subscription, consumer.id());
+            assert_eq!(event.stream.as_deref(), Some("btcusdt@bookTicker"));
+            assert!(matches!(event.event, WebsocketEvent::BookTicker(_)));
+        }
+        assert!(tokio::time::timeout(Duration::from_millis(200), trades.try_next()).await.is_err());
+
+        assert!(ws.unsubscribe(tickers.id()).await.unwrap());
+        assert!(tickers.try_next().await.unwrap().is_none());
+        assert!(ws.events(tickers.id()).is_none());
+    }
+
+    #[tokio::test]
+    async fn shares_connections_until_a_limit_is_reached() {
+        let server = MockBinanceServer::start().await.unwrap();
+        let mut ws = Websocket::new_with_endpoint(server.ws_endpoint().into());
+        ws.subscribe(WebsocketStreamType::IndividualTrade(vec!["BTCUSDT".into(), "ETHBTC".into()])).await.unwrap();
+        ws.subscribe(WebsocketStreamType::BookTicker(vec!["BTCUSDT".into()])).await.unwrap();
+        assert_eq!(ws.connection_count(), 1);
+        assert_eq!(server.connection_count(), 1);
+        let mut streams = ws.list_subscriptions().await.unwrap();
+        streams.sort();
+        assert_eq!(streams, vec!["btcusdt@bookTicker", "btcusdt@trade", "ethbtc@trade"]);
+
+        let mut ws = Websocket::new_with_endpoint(server.ws_endpoint().into()).set_connection_limits(2, 5);
+        ws.subscribe(WebsocketStreamType::IndividualTrade(vec!["BTCUSDT".into(), "ETHBTC".into()])).await.unwrap();
+        ws.subscribe(WebsocketStreamType::IndividualTrade(vec!["BTCUSDT".into()])).await.unwrap();
+        assert_eq!(ws.connection_count(), 1, "streams already carried do not count twice");
+        ws.subscribe(WebsocketStreamType::BookTicker(vec!["BTCUSDT".into()])).await.unwrap();
+        assert_eq!(ws.connection_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn spreads_large_subscriptions_and_waits_for_the_message_budget() {
+        let server = MockBinanceServer::start().await.unwrap();
+        let mut ws = Websocket::new_with_endpoint(server.ws_endpoint().into()).set_connection_limits(2, 5);
+        let symbols: Vec<String> = ["BTCUSDT", "ETHBTC", "BNBBTC", "XRPBTC", "LTCBTC"].iter().map(|s| s.to_string()).collect();
+        let mut trades = ws.subscribe(WebsocketStreamType::IndividualTrade(symbols)).await.unwrap();
+        assert_eq!(ws.connection_count(), 3);
+        let mut streams = ws.list_subscriptions().await.unwrap();
+        streams.sort();
+        assert_eq!(streams, vec!["bnbbtc@trade", "btcusdt@trade", "ethbtc@trade", "ltcbtc@trade", "xrpbtc@trade"]);
+
+        server.push_event("ltcbtc@trade", json!({
+            "e": "trade", "E": 123456789, "s": "LTCBTC", "t": 1, "p": "0.001", "q": "100",
+            "b": 88, "a": 50, "T": 123456785, "m": true, "M": true
+        }));
+        match trades.try_next().await.unwrap().unwrap().event {
+            WebsocketEvent::IndividualTrade(trade) => assert_eq!(trade.symbol, "LTCBTC"),
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert!(ws.unsubscribe(trades.id()).await.unwrap());
+        assert_eq!(ws.connection_count(), 0);
+
+        let mut ws = Websocket::new_with_endpoint(server.ws_endpoint().into()).set_connection_limits(1024, 2);
+        let started = Instant::now();
+        for symbol in ["BTCUSDT", "ETHBTC", "BNBBTC"] {
+            ws.subscribe(WebsocketStreamType::BookTicker(vec![symbol.into()])).await.unwrap();
+        }
+        assert_eq!(ws.connection_count(), 1, "a busy connection is waited for, not replaced");
+        assert!(started.elapsed() >= Duration::from_millis(900));
+    }
+
+    #[tokio::test]
+    async fn unsubscribes_streams_and_closes_unused_connections() {
+        let server = MockBinanceServer::start().await.unwrap();
+        let mut ws = Websocket::new_with_endpoint(server.ws_endpoint().into());
+        let trades = ws.subscribe(WebsocketStreamType::IndividualTrade(vec!["BTCUSDT".into(), "ETHBTC".into()])).await.unwrap().id();
+        let tickers = ws.subscribe(WebsocketStreamType::BookTicker(vec!["BTCUSDT".into()])).await.unwrap().id();
+
+        assert!(ws.unsubscribe_symbols(trades, &["ETHBTC".into()]).await.unwrap());
+        assert!(ws.unsubscribe(tickers).await.unwrap());
+        assert!(!ws.unsubscribe(tickers).await.unwrap());
+        assert_eq!(ws.list_subscriptions().await.unwrap(), vec!["btcusdt@trade"]);
+
+        assert!(ws.unsubscribe(trades).await.unwrap());
+        assert_eq!(ws.connection_count(), 0);
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while server.connection_count() > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the connection was not closed");
+    }
+
+    fn parse_fixture(name: &str) -> WebsocketEvent {
+        let path = format!("{}/tests/fixtures/websocket/{}.json", env!("CARGO_MANIFEST_DIR"), name);
+        let fixture = std::fs::read_to_string(&path).unwrap();
+        Websocket::new().parse_response_type(&fixture).unwrap_or_else(|e| panic!("{}: {}", name, e))
+    }
+
+    #[test]
+    fn parses_every_event_type() {
+        assert!(matches!(parse_fixture("trade"), WebsocketEvent::IndividualTrade(e) if e.symbol == "BNBBTC"));
+        assert!(matches!(parse_fixture("agg_trade"), WebsocketEvent::AggregatedTrades(e) if e.aggregated_trade_id == 12345));
+        assert!(matches!(parse_fixture("kline"), WebsocketEvent::Kline(e) if e.kline.interval == "1m"));
+        assert!(matches!(parse_fixture("ticker"), WebsocketEvent::TwentyFourHourTicker(e) if e.num_trades == 18151));
+        assert!(matches!(parse_fixture("all_tickers"), WebsocketEvent::DayTickerAll(e) if e.len() == 1));
+        assert!(matches!(parse_fixture("book_ticker"), WebsocketEvent::BookTicker(e) if e.update_id == 400900217));
+        assert!(matches!(parse_fixture("partial_depth"), WebsocketEvent::PartialBookDepthStream { symbol, order_book }
+            if symbol == "BNBBTC" && order_book.last_update_id == 160 && order_book.bids.len() == 1));
+        assert!(matches!(parse_fixture("diff_depth"), WebsocketEvent::DiffDepthStream(e)
+            if e.first_update_id == 157 && e.final_update_id == 160 && e.asks.len() == 1));
+        assert!(matches!(parse_fixture("execution_report"), WebsocketEvent::OrderUpdate(e) if e.order_id == 4293153
+            && e.execution_type == ExecutionType::New && e.order_status == OrderStatus::New && e.commission_asset.is_none()));
+        assert!(matches!(parse_fixture("execution_report_trade"), WebsocketEvent::OrderUpdate(e)
+            if e.order_status == OrderStatus::Filled && e.commission_asset.as_deref() == Some("BNB") && e.order_list_id == 2));
+        assert!(matches!(parse_fixture("account_position"), WebsocketEvent::AccountPosition(e) if e.balances[0].asset == "ETH"));
+        assert!(matches!(parse_fixture("list_status"), WebsocketEvent::ListStatus(e) if e.orders.len() == 2 && e.list_order_status == "EXECUTING"));
+        assert!(matches!(parse_fixture("external_lock_update"), WebsocketEvent::ExternalLockUpdate(e) if e.asset == "NEO"));
+        assert!(matches!(parse_fixture("listen_key_expired"), WebsocketEvent::ListenKeyExpired(e) if e.listen_key.starts_with("OfYGbUzi")));
+        assert!(matches!(parse_fixture("balance_update"), WebsocketEvent::BalanceUpdate(e) if e.asset == "BTC"));
+        assert!(matches!(parse_fixture("account_info"), WebsocketEvent::AccountUpdate(e) if e.balance.len() == 1));
+        assert!(matches!(parse_fixture("all_book_tickers"), WebsocketEvent::BookTicker(e) if e.symbol == "BNBUSDT"));
+        assert!(matches!(parse_fixture("mini_ticker"), WebsocketEvent::MiniTicker(e) if e.symbol == "BNBBTC"));
+        assert!(matches!(parse_fixture("all_mini_tickers"), WebsocketEvent::MiniTickerAll(e) if e.len() == 2));
+        assert!(matches!(parse_fixture("rolling_window_ticker"), WebsocketEvent::RollingWindowTicker(e) if e.event_type == "1hTicker"));
+        assert!(matches!(parse_fixture("all_rolling_window_tickers"), WebsocketEvent::RollingWindowTickerAll(e) if e.len() == 1));
+        assert!(matches!(parse_fixture("avg_price"), WebsocketEvent::AveragePrice(e) if e.interval == "5m"));
+        assert!(matches!(parse_fixture("unknown"), WebsocketEvent::Raw { stream: Some(s), .. } if s == "bnbbtc@somethingNew"));
+    }
+
+    #[tokio::test]
+    async fn malformed_payloads_and_empty_symbol_lists_are_errors() {
+        let msg = r#"{"stream":"btcusdt@trade","data":{"e":"trade","s":"BTCUSDT","p":"not a trade"}}"#;
+        assert!(matches!(Websocket::new().parse_response_type(msg), Err(BinanceErr::MalformedPayload { payload, .. }) if payload == msg));
+        assert!(matches!(Websocket::new().parse_response_type("{"), Err(BinanceErr::MalformedPayload { .. })));
+
+        assert!(WebsocketStreamType::IndividualTrade(vec![]).first().is_err());
+        assert_eq!(WebsocketStreamType::MiniTickerAll.first().unwrap(), "!miniTicker@arr");
+        assert!(Websocket::new().subscribe(WebsocketStreamType::BookTicker(vec![])).await.is_err());
+    }
+
+    #[test]
+    fn symbols_do_not_decide_the_event_type() {
+        let msg = r#"{"stream":"tradeusdt@bookTicker","data":{"u":1,"s":"TRADEUSDT","b":"1.0","B":"2.0","a":"1.1","A":"3.0"}}"#;
+        assert!(matches!(Websocket::new().parse_response_type(msg).unwrap(), WebsocketEvent::BookTicker(e) if e.symbol == "TRADEUSDT"));
+    }
+
+    #[test]
+    fn depth_stream_names() {
+        let partial = WebsocketStreamType::PartialBookDepthStream {
+            symbols: vec!["BNBBTC".into()],
+            levels: DepthLevels::Ten,
+            speed: UpdateSpeed::Ms100,
+        };
+        assert_eq!(ExchangeSettings::map_symbols_to_stream_params(partial), vec!["bnbbtc@depth10@100ms"]);
+        let diff = WebsocketStreamType::DiffDepthStream { symbols: vec!["BNBBTC".into()], speed: UpdateSpeed::Ms1000 };
+        assert_eq!(ExchangeSettings::map_symbols_to_stream_params(diff), vec!["bnbbtc@depth"]);
+    }
+
+    #[test]
+    fn ticker_and_kline_stream_names() {
+        let stream_names = |stream_type| ExchangeSettings::map_symbols_to_stream_params(stream_type);
+        assert_eq!(stream_names(WebsocketStreamType::MiniTickerAll), vec!["!miniTicker@arr"]);
+        assert_eq!(stream_names(WebsocketStreamType::BookTickerAll), vec!["!bookTicker"]);
+        assert_eq!(stream_names(WebsocketStreamType::AveragePrice(vec!["BTCUSDT".into()])), vec!["btcusdt@avgPrice"]);
+        let rolling = WebsocketStreamType::RollingWindowTicker { symbols: vec!["BNBBTC".into()], window: RollingWindow::FourHours };
+        assert_eq!(stream_names(rolling), vec!["bnbbtc@ticker_4h"]);
+        assert_eq!(stream_names(WebsocketStreamType::RollingWindowTickerAll(RollingWindow::OneDay)), vec!["!ticker_1d@arr"]);
+        let kline = WebsocketStreamType::KlineWithTimezone {
+            symbols: vec!["BNBBTC".into()],
+            interval: KlineInterval::Hours(1),
+            timezone: "+08:00".into(),
+        };
+        assert_eq!(stream_names(kline), vec!["bnbbtc@kline_1h@+08:00"]);
     }
 }